@@ -1,5 +1,11 @@
+use std::backtrace::Backtrace;
+use std::backtrace::BacktraceStatus;
+use std::cmp::Ordering;
 use std::fmt::Debug;
 use std::fmt::Display;
+use std::hash::Hash;
+use std::hash::Hasher;
+use std::ops::Deref;
 use std::sync::Arc;
 
 /// An Arc'ed anyhow error.
@@ -15,6 +21,11 @@ use std::sync::Arc;
 /// Instead one would send 1 request and have all tasks that need it wait of the result.
 /// However, this means that both X and the error must be clonable.
 /// X can be make Clone with Arc<X>, and the error can be made clone with ArcAnyhowError.
+///
+/// # Default
+/// The default value is a sentinel error with the message "uninitialized",
+/// so that this can be used in structs that derive `Default`.
+/// It is not a real error, and should not be compared against real errors.
 #[derive(Clone)]
 pub struct ArcAnyhowError(Arc<anyhow::Error>);
 
@@ -23,6 +34,98 @@ impl ArcAnyhowError {
     pub fn new(error: anyhow::Error) -> Self {
         Self(Arc::new(error))
     }
+
+    /// Returns true if both errors point to the same error object.
+    ///
+    /// Unlike `==`, which compares error messages,
+    /// this can tell apart different errors that happen to have the same message.
+    pub fn ptr_eq(a: &Self, b: &Self) -> bool {
+        Arc::ptr_eq(&a.0, &b.0)
+    }
+
+    /// Returns true if `E` is the type held by this error object.
+    ///
+    /// See [`anyhow::Error::is`].
+    pub fn is<E>(&self) -> bool
+    where
+        E: Display + Debug + Send + Sync + 'static,
+    {
+        self.0.is::<E>()
+    }
+
+    /// Iterate over the chain of errors, starting with this error's inner error.
+    ///
+    /// See [`anyhow::Error::chain`].
+    pub fn chain(&self) -> anyhow::Chain<'_> {
+        self.0.chain()
+    }
+
+    /// Wrap this error in an [`anyhow::Error`] with additional context, which is only evaluated when called.
+    ///
+    /// This is like [`anyhow::Context::with_context`],
+    /// and is useful when the context is expensive to make, like when formatting a large path.
+    pub fn with_context<C, F>(self, f: F) -> anyhow::Error
+    where
+        C: Display + Send + Sync + 'static,
+        F: FnOnce() -> C,
+    {
+        anyhow::Error::new(self).context(f())
+    }
+
+    /// Get the backtrace of this error, if one was captured.
+    ///
+    /// See [`anyhow::Error::backtrace`].
+    /// Unlike anyhow, this returns `None` instead of an empty backtrace if capturing was disabled or unsupported,
+    /// like when the `RUST_BACKTRACE` and `RUST_LIB_BACKTRACE` environment variables are unset.
+    pub fn backtrace(&self) -> Option<&Backtrace> {
+        let backtrace = self.0.backtrace();
+        if backtrace.status() != BacktraceStatus::Captured {
+            return None;
+        }
+
+        Some(backtrace)
+    }
+
+    /// Get the lowest level cause of this error.
+    ///
+    /// See [`anyhow::Error::root_cause`].
+    pub fn root_cause(&self) -> &(dyn std::error::Error + 'static) {
+        self.0.root_cause()
+    }
+
+    /// Get this error as a value that can be recorded by `tracing`,
+    /// like `tracing::error!(error = error.as_tracing_value(), "failed")`.
+    ///
+    /// `tracing::Value` is sealed, so it can't be implemented for this type directly.
+    /// Instead, this returns the error as a `dyn Error`, which `tracing` records as an error with its source chain.
+    #[cfg(feature = "tracing")]
+    pub fn as_tracing_value(&self) -> &(dyn std::error::Error + Send + Sync + 'static) {
+        self
+    }
+}
+
+impl From<anyhow::Error> for ArcAnyhowError {
+    fn from(error: anyhow::Error) -> Self {
+        Self::new(error)
+    }
+}
+
+// This allows calling any `anyhow::Error` method, like `downcast_ref`, without wrapping each one.
+impl Deref for ArcAnyhowError {
+    type Target = anyhow::Error;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+// This is a sentinel for types that need to derive `Default`, not a real error.
+// Since comparisons use the message, it compares equal to any real error with the message "uninitialized",
+// so do not use `==` with a default error to check whether an error was set.
+impl Default for ArcAnyhowError {
+    fn default() -> Self {
+        Self::new(anyhow::anyhow!("uninitialized"))
+    }
 }
 
 impl Debug for ArcAnyhowError {
@@ -37,6 +140,94 @@ impl Display for ArcAnyhowError {
     }
 }
 
+// Comparisons are performed on the error message, not the error value.
+// This is lossy, as two different errors with the same message will compare equal,
+// but it allows errors to be placed into sorted containers for deduplication.
+impl PartialEq for ArcAnyhowError {
+    fn eq(&self, other: &Self) -> bool {
+        self.to_string() == other.to_string()
+    }
+}
+
+impl Eq for ArcAnyhowError {}
+
+impl PartialOrd for ArcAnyhowError {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for ArcAnyhowError {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.to_string().cmp(&other.to_string())
+    }
+}
+
+// Like the comparisons, hashing uses the error message.
+// Different errors with the same message will have the same hash.
+impl Hash for ArcAnyhowError {
+    fn hash<H>(&self, state: &mut H)
+    where
+        H: Hasher,
+    {
+        self.to_string().hash(state);
+    }
+}
+
+// Errors are serialized as their message.
+// As a result, deserializing an error only recovers its message.
+#[cfg(feature = "serde")]
+impl serde::Serialize for ArcAnyhowError {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serializer.collect_str(self)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for ArcAnyhowError {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        let message = String::deserialize(deserializer)?;
+        Ok(Self::new(anyhow::anyhow!(message)))
+    }
+}
+
+// Errors are logged as their error chain, like "outer: inner".
+#[cfg(feature = "slog")]
+impl slog::Value for ArcAnyhowError {
+    fn serialize(
+        &self,
+        _record: &slog::Record<'_>,
+        key: slog::Key,
+        serializer: &mut dyn slog::Serializer,
+    ) -> slog::Result {
+        serializer.emit_arguments(key, &format_args!("{self:#}"))
+    }
+}
+
+// As a key-value pair, the error is logged under the key "error".
+#[cfg(feature = "slog")]
+impl slog::KV for ArcAnyhowError {
+    fn serialize(
+        &self,
+        record: &slog::Record<'_>,
+        serializer: &mut dyn slog::Serializer,
+    ) -> slog::Result {
+        slog::Value::serialize(self, record, "error", serializer)
+    }
+}
+
+// anyhow errors carry no diagnostic information like codes or labels,
+// so only the defaults are used.
+// miette still reports the whole error chain through `source`.
+#[cfg(feature = "miette")]
+impl miette::Diagnostic for ArcAnyhowError {}
+
 // We allow deprecated functions as this is just a wrapper,
 // we want to emulate anyhow::error's choices,
 // even if they use deprecated code
@@ -55,3 +246,217 @@ impl std::error::Error for ArcAnyhowError {
         self.0.as_ref().cause()
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::collections::BTreeSet;
+    use std::collections::HashSet;
+
+    #[test]
+    fn default_is_sentinel() {
+        #[derive(Default)]
+        struct State {
+            error: ArcAnyhowError,
+        }
+
+        let state = State::default();
+        assert!(state.error.to_string() == "uninitialized");
+        assert!(!ArcAnyhowError::ptr_eq(
+            &state.error,
+            &ArcAnyhowError::default()
+        ));
+    }
+
+    #[test]
+    fn ord_uses_message() {
+        let a = ArcAnyhowError::new(anyhow::anyhow!("a"));
+        let b = ArcAnyhowError::new(anyhow::anyhow!("b"));
+        let a_copy = ArcAnyhowError::new(anyhow::anyhow!("a"));
+
+        assert!(a < b);
+        assert!(a == a_copy);
+
+        let set: BTreeSet<_> = [b.clone(), a.clone(), a_copy].into_iter().collect();
+        assert!(set.into_iter().collect::<Vec<_>>() == [a, b]);
+    }
+
+    #[test]
+    fn ptr_eq_works() {
+        let a = ArcAnyhowError::new(anyhow::anyhow!("a"));
+        let a_clone = a.clone();
+        let a_copy = ArcAnyhowError::new(anyhow::anyhow!("a"));
+
+        assert!(ArcAnyhowError::ptr_eq(&a, &a_clone));
+        assert!(!ArcAnyhowError::ptr_eq(&a, &a_copy));
+    }
+
+    #[test]
+    fn hash_uses_message() {
+        let a = ArcAnyhowError::new(anyhow::anyhow!("a"));
+        let a_copy = ArcAnyhowError::new(anyhow::anyhow!("a"));
+        let b = ArcAnyhowError::new(anyhow::anyhow!("b"));
+
+        let set: HashSet<_> = [a, a_copy, b].into_iter().collect();
+        assert!(set.len() == 2);
+    }
+
+    #[test]
+    fn chain_works() {
+        let error = ArcAnyhowError::new(anyhow::anyhow!("inner").context("outer"));
+        let messages: Vec<_> = error.chain().map(|error| error.to_string()).collect();
+
+        assert!(messages == ["outer", "inner"]);
+    }
+
+    #[test]
+    fn deref_works() {
+        let error = ArcAnyhowError::new(anyhow::Error::new(std::fmt::Error).context("outer"));
+        let inner: &anyhow::Error = &error;
+
+        assert!(inner.to_string() == "outer");
+        assert!(error.downcast_ref::<std::fmt::Error>().is_some());
+    }
+
+    #[test]
+    fn with_context_works() {
+        let error = ArcAnyhowError::new(anyhow::anyhow!("inner"));
+        let error = error.with_context(|| format!("outer {}", 1));
+        let messages: Vec<_> = error.chain().map(|error| error.to_string()).collect();
+
+        assert!(messages == ["outer 1", "inner"]);
+    }
+
+    #[test]
+    fn root_cause_works() {
+        let error = ArcAnyhowError::new(anyhow::anyhow!("inner").context("outer"));
+
+        assert!(error.root_cause().to_string() == "inner");
+    }
+
+    #[test]
+    fn from_works() {
+        fn fails() -> Result<(), ArcAnyhowError> {
+            Err(anyhow::anyhow!("error"))?;
+            Ok(())
+        }
+
+        assert!(fails().expect_err("did not fail").to_string() == "error");
+    }
+
+    #[test]
+    fn is_works() {
+        let error = ArcAnyhowError::new(anyhow::Error::new(std::fmt::Error));
+
+        assert!(error.is::<std::fmt::Error>());
+        assert!(!error.is::<std::io::Error>());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_round_trip() {
+        let error = ArcAnyhowError::new(anyhow::anyhow!("inner").context("outer"));
+
+        let json = serde_json::to_string(&error).expect("failed to serialize");
+        assert!(json == "\"outer\"");
+
+        let error: ArcAnyhowError = serde_json::from_str(&json).expect("failed to deserialize");
+        assert!(error.to_string() == "outer");
+    }
+
+    #[cfg(feature = "slog")]
+    #[test]
+    fn slog_logs_chain() {
+        struct TestSerializer(Vec<(slog::Key, String)>);
+
+        impl slog::Serializer for TestSerializer {
+            fn emit_arguments(
+                &mut self,
+                key: slog::Key,
+                value: &std::fmt::Arguments<'_>,
+            ) -> slog::Result {
+                self.0.push((key, value.to_string()));
+                Ok(())
+            }
+        }
+
+        let error = ArcAnyhowError::new(anyhow::anyhow!("inner").context("outer"));
+        let record_static = slog::record_static!(slog::Level::Error, "");
+        let mut serializer = TestSerializer(Vec::new());
+        slog::KV::serialize(
+            &error,
+            &slog::Record::new(&record_static, &format_args!("failed"), slog::b!()),
+            &mut serializer,
+        )
+        .expect("failed to serialize");
+
+        assert!(serializer.0 == [("error", String::from("outer: inner"))]);
+    }
+
+    #[cfg(feature = "tracing")]
+    #[test]
+    fn tracing_records_error() {
+        use std::sync::Mutex;
+        use tracing::field::Field;
+        use tracing::field::Visit;
+        use tracing::span;
+        use tracing::Event;
+        use tracing::Metadata;
+
+        #[derive(Default)]
+        struct TestSubscriber(Mutex<Vec<String>>);
+
+        struct TestVisitor<'a>(&'a mut Vec<String>);
+
+        impl Visit for TestVisitor<'_> {
+            fn record_debug(&mut self, _field: &Field, _value: &dyn Debug) {}
+
+            fn record_error(&mut self, field: &Field, value: &(dyn std::error::Error + 'static)) {
+                self.0.push(format!("{}={value}", field.name()));
+                if let Some(source) = value.source() {
+                    self.0.push(format!("source={source}"));
+                }
+            }
+        }
+
+        impl tracing::Subscriber for TestSubscriber {
+            fn enabled(&self, _metadata: &Metadata<'_>) -> bool {
+                true
+            }
+
+            fn new_span(&self, _span: &span::Attributes<'_>) -> span::Id {
+                span::Id::from_u64(1)
+            }
+
+            fn record(&self, _span: &span::Id, _values: &span::Record<'_>) {}
+
+            fn record_follows_from(&self, _span: &span::Id, _follows: &span::Id) {}
+
+            fn event(&self, event: &Event<'_>) {
+                event.record(&mut TestVisitor(&mut self.0.lock().unwrap()));
+            }
+
+            fn enter(&self, _span: &span::Id) {}
+
+            fn exit(&self, _span: &span::Id) {}
+        }
+
+        let error = ArcAnyhowError::new(anyhow::anyhow!("inner").context("outer"));
+        let subscriber = std::sync::Arc::new(TestSubscriber::default());
+        tracing::subscriber::with_default(subscriber.clone(), || {
+            tracing::error!(error = error.as_tracing_value());
+        });
+
+        assert!(*subscriber.0.lock().unwrap() == ["error=outer", "source=inner"]);
+    }
+
+    #[cfg(feature = "miette")]
+    #[test]
+    fn miette_reports_chain() {
+        let error = ArcAnyhowError::new(anyhow::anyhow!("inner").context("outer"));
+        let report = miette::Report::new(error);
+        let messages: Vec<_> = report.chain().map(|error| error.to_string()).collect();
+
+        assert!(messages == ["outer", "inner"]);
+    }
+}
@@ -0,0 +1,199 @@
+use anyhow::Context;
+use std::ops::Deref;
+use std::ops::DerefMut;
+use tokio::fs::File;
+
+/// An advisory lock on a tokio file.
+///
+/// This is the async version of [`FileLock`](crate::FileLock).
+/// Locking is done through a duplicate handle of the file,
+/// so that waiting for the lock can happen on a blocking thread instead of blocking the runtime.
+///
+/// This is only available on platforms that support advisory locking, which are currently Unix and Windows.
+#[derive(Debug)]
+pub struct AsyncFileLock {
+    /// The file
+    file: File,
+}
+
+impl AsyncFileLock {
+    /// Make a new [`AsyncFileLock`] for a file.
+    ///
+    /// This does not lock the file.
+    pub fn new(file: File) -> Self {
+        Self { file }
+    }
+
+    /// Try to lock the file, without blocking.
+    ///
+    /// # Return
+    /// Returns an error if the file is already locked or could not be locked.
+    pub fn try_lock(&mut self) -> std::io::Result<AsyncFileLockGuard<'_>> {
+        let handle = duplicate_handle(&self.file)?;
+        handle.try_lock()?;
+
+        Ok(AsyncFileLockGuard {
+            file: &mut self.file,
+            handle,
+        })
+    }
+
+    /// Lock the file, waiting until the lock is available.
+    ///
+    /// The wait happens on a blocking thread through [`tokio::task::spawn_blocking`].
+    /// This is cancel safe, as the blocking thread releases the lock
+    /// if it gets it after the returned future was dropped.
+    ///
+    /// # Return
+    /// Returns an error if the file could not be locked.
+    pub async fn lock_async(&mut self) -> anyhow::Result<AsyncFileLockGuard<'_>> {
+        let handle = duplicate_handle(&self.file).context("failed to duplicate file handle")?;
+        let (tx, rx) = tokio::sync::oneshot::channel();
+        tokio::task::spawn_blocking(move || {
+            let result = handle.lock().map(|()| handle);
+
+            // If the future was dropped while waiting, nothing would release the lock.
+            if let Err(Ok(handle)) = tx.send(result) {
+                let _ = handle.unlock();
+            }
+        });
+        let handle = rx
+            .await
+            .context("lock task failed")?
+            .context("failed to lock file")?;
+
+        Ok(AsyncFileLockGuard {
+            file: &mut self.file,
+            handle,
+        })
+    }
+
+    /// Get the file back.
+    pub fn into_inner(self) -> File {
+        self.file
+    }
+}
+
+/// A guard for a locked [`AsyncFileLock`].
+///
+/// The file is unlocked when this is dropped.
+#[derive(Debug)]
+pub struct AsyncFileLockGuard<'a> {
+    /// The file
+    file: &'a mut File,
+
+    /// The duplicate handle that holds the lock.
+    handle: std::fs::File,
+}
+
+impl Deref for AsyncFileLockGuard<'_> {
+    type Target = File;
+
+    fn deref(&self) -> &Self::Target {
+        self.file
+    }
+}
+
+impl DerefMut for AsyncFileLockGuard<'_> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.file
+    }
+}
+
+impl Drop for AsyncFileLockGuard<'_> {
+    fn drop(&mut self) {
+        // On Unix, the lock is shared with the file,
+        // so it must be released explicitly instead of by closing the handle.
+        //
+        // Unlocking a lock we hold should not fail,
+        // and there is nothing we could do about it if it did.
+        let _ = self.handle.unlock();
+    }
+}
+
+/// Make a new std handle for the same file.
+fn duplicate_handle(file: &File) -> std::io::Result<std::fs::File> {
+    cfg_if::cfg_if! {
+        if #[cfg(unix)] {
+            use std::os::fd::AsFd;
+
+            let handle = file.as_fd().try_clone_to_owned()?;
+        } else {
+            use std::os::windows::io::AsHandle;
+
+            let handle = file.as_handle().try_clone_to_owned()?;
+        }
+    }
+
+    Ok(handle.into())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::time::Duration;
+    use tokio::io::AsyncWriteExt;
+
+    #[tokio::test]
+    async fn async_file_lock_sanity_check() {
+        tokio::fs::create_dir_all("test_tmp")
+            .await
+            .expect("failed to create tmp dir");
+
+        let path = "test_tmp/async_file_lock.txt";
+        let mut lock = AsyncFileLock::new(File::create(path).await.expect("failed to create file"));
+        let mut other_lock =
+            AsyncFileLock::new(File::open(path).await.expect("failed to open file"));
+
+        let mut guard = lock.try_lock().expect("failed to lock");
+        guard.write_all(b"data").await.expect("failed to write");
+        guard.flush().await.expect("failed to flush");
+        other_lock.try_lock().expect_err("locked a locked file");
+
+        // The other lock should wait until the first guard is dropped.
+        let waiter = tokio::spawn(async move {
+            other_lock.lock_async().await.expect("failed to lock");
+        });
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(!waiter.is_finished(), "locked a locked file");
+        drop(guard);
+        tokio::time::timeout(Duration::from_secs(5), waiter)
+            .await
+            .expect("timed out waiting for task")
+            .expect("failed to join task");
+
+        drop(lock.into_inner());
+        assert!(tokio::fs::read(path).await.expect("failed to read") == b"data");
+    }
+
+    #[tokio::test]
+    async fn lock_async_cancel_releases_lock() {
+        tokio::fs::create_dir_all("test_tmp")
+            .await
+            .expect("failed to create tmp dir");
+
+        let path = "test_tmp/async_file_lock_cancel.txt";
+        let mut lock = AsyncFileLock::new(File::create(path).await.expect("failed to create file"));
+        let mut other_lock =
+            AsyncFileLock::new(File::open(path).await.expect("failed to open file"));
+
+        // Give up waiting for the other lock while the first is held.
+        let guard = lock.try_lock().expect("failed to lock");
+        tokio::time::timeout(Duration::from_millis(50), other_lock.lock_async())
+            .await
+            .expect_err("locked a locked file");
+
+        // The abandoned wait gets the lock once it is free, and must release it again.
+        drop(guard);
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        tokio::time::timeout(Duration::from_secs(5), async {
+            while lock.try_lock().is_err() {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .expect("the abandoned wait kept the lock");
+
+        drop(other_lock);
+    }
+}
@@ -0,0 +1,103 @@
+use std::hash::BuildHasher;
+use std::hash::Hasher;
+use std::time::Duration;
+
+/// An iterator of exponentially increasing delays, for waiting between retries.
+///
+/// The first delay is the base delay, and each delay after that is double the last, up to the max delay.
+/// This never runs out, so use [`Iterator::take`] to limit the number of retries.
+#[derive(Debug, Clone)]
+pub struct Backoff {
+    /// The first delay.
+    base: Duration,
+
+    /// The largest delay.
+    max: Duration,
+
+    /// Whether delays should be randomized.
+    jitter: bool,
+
+    /// The number of delays that have been yielded.
+    attempt: u32,
+}
+
+impl Backoff {
+    /// Make a new [`Backoff`] with the given base and max delays, without jitter.
+    pub fn new(base: Duration, max: Duration) -> Self {
+        Self {
+            base,
+            max,
+            jitter: false,
+            attempt: 0,
+        }
+    }
+
+    /// Set whether delays should be randomized.
+    ///
+    /// With jitter, each delay is a random duration between half of the delay and the full delay.
+    /// This spreads out retries from many clients that failed at the same time.
+    ///
+    /// Defaults to false.
+    pub fn jitter(mut self, jitter: bool) -> Self {
+        self.jitter = jitter;
+        self
+    }
+}
+
+impl Iterator for Backoff {
+    type Item = Duration;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let delay = 2_u32
+            .checked_pow(self.attempt)
+            .and_then(|factor| self.base.checked_mul(factor))
+            .map_or(self.max, |delay| delay.min(self.max));
+        self.attempt = self.attempt.saturating_add(1);
+
+        if !self.jitter {
+            return Some(delay);
+        }
+
+        // A new `RandomState` has new random keys, which is enough randomness for jitter.
+        let random = std::hash::RandomState::new().build_hasher().finish();
+        let delay = u64::try_from(delay.as_nanos()).unwrap_or(u64::MAX);
+        let half = delay / 2;
+        let jitter = random % (delay - half).saturating_add(1);
+
+        Some(Duration::from_nanos(half + jitter))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn backoff_doubles_until_max() {
+        let delays: Vec<_> = Backoff::new(Duration::from_secs(1), Duration::from_secs(5))
+            .take(5)
+            .collect();
+        assert!(
+            delays
+                == [1, 2, 4, 5, 5]
+                    .into_iter()
+                    .map(Duration::from_secs)
+                    .collect::<Vec<_>>()
+        );
+
+        // Huge attempts shouldn't overflow.
+        let delay = Backoff::new(Duration::from_secs(1), Duration::from_secs(5))
+            .nth(100)
+            .unwrap();
+        assert!(delay == Duration::from_secs(5));
+    }
+
+    #[test]
+    fn backoff_jitter_stays_in_range() {
+        let backoff = Backoff::new(Duration::from_secs(1), Duration::from_secs(5)).jitter(true);
+        for (delay, expected) in backoff.zip([1, 2, 4, 5, 5]) {
+            let expected = Duration::from_secs(expected);
+            assert!(delay >= expected / 2 && delay <= expected);
+        }
+    }
+}
@@ -0,0 +1,125 @@
+use crate::download_to_writer;
+use crate::ArcAnyhowError;
+use anyhow::Context;
+use bytes::Bytes;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::sync::Mutex;
+use tokio::fs::File;
+use tokio::io::AsyncWriteExt;
+use tokio::sync::OnceCell;
+
+/// The shared result of a download.
+type DownloadCell = Arc<OnceCell<Result<Bytes, ArcAnyhowError>>>;
+
+/// A downloader that only sends one request for concurrent downloads of the same url.
+///
+/// While a download is in flight, other downloads of the same url wait for it and share its result.
+/// Since the result is shared, the body is buffered in memory before it is written to each file.
+/// Results are not cached, so a download that starts after the last one finished sends a new request.
+#[derive(Debug, Clone)]
+pub struct DeduplicatingDownloader {
+    /// The client
+    client: reqwest::Client,
+
+    /// The downloads that are in flight, by url.
+    in_flight: Arc<Mutex<HashMap<String, DownloadCell>>>,
+}
+
+impl DeduplicatingDownloader {
+    /// Make a new [`DeduplicatingDownloader`] that downloads with the given client.
+    pub fn new(client: reqwest::Client) -> Self {
+        Self {
+            client,
+            in_flight: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    /// Download a url, sharing the request with any other in-flight downloads of the same url.
+    ///
+    /// If the task that is sending the request is cancelled, one of the waiting tasks sends it instead.
+    pub async fn download(&self, url: &str) -> Result<Bytes, ArcAnyhowError> {
+        let cell = self
+            .in_flight
+            .lock()
+            .unwrap_or_else(|error| error.into_inner())
+            .entry(url.into())
+            .or_default()
+            .clone();
+
+        let result = cell
+            .get_or_init(|| async {
+                let mut buffer = Vec::new();
+                download_to_writer(&self.client, url, &mut buffer)
+                    .await
+                    .map(|()| Bytes::from(buffer))
+                    .map_err(ArcAnyhowError::new)
+            })
+            .await
+            .clone();
+
+        // Stop sharing this download, unless a new one already replaced it.
+        let mut in_flight = self
+            .in_flight
+            .lock()
+            .unwrap_or_else(|error| error.into_inner());
+        if in_flight
+            .get(url)
+            .is_some_and(|in_flight_cell| Arc::ptr_eq(in_flight_cell, &cell))
+        {
+            in_flight.remove(url);
+        }
+
+        result
+    }
+
+    /// Download a url to a tokio file, sharing the request with any other in-flight downloads of the same url.
+    ///
+    /// See [`DeduplicatingDownloader::download`].
+    pub async fn download_to_file(&self, url: &str, file: &mut File) -> anyhow::Result<()> {
+        let bytes = self.download(url).await?;
+
+        file.write_all(&bytes)
+            .await
+            .context("failed to write to file")?;
+        file.flush().await.context("failed to flush file")?;
+        file.sync_all().await.context("failed to sync file data")?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_server::response;
+    use crate::test_server::TestServer;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn deduplicates_concurrent_downloads() {
+        // This server can only answer one request.
+        let server = TestServer::spawn([response("200 OK", &[], b"data")]);
+        let url = format!("{}/file", server.url);
+
+        let downloader = DeduplicatingDownloader::new(reqwest::Client::new());
+        let tasks: Vec<_> = (0..4)
+            .map(|_| {
+                let downloader = downloader.clone();
+                let url = url.clone();
+                tokio::spawn(async move { downloader.download(&url).await })
+            })
+            .collect();
+        for task in tasks {
+            let bytes = tokio::time::timeout(Duration::from_secs(5), task)
+                .await
+                .expect("timed out waiting for task")
+                .expect("failed to join task")
+                .expect("failed to download");
+            assert!(bytes == b"data"[..]);
+        }
+
+        assert!(server.join().len() == 1);
+        assert!(downloader.in_flight.lock().unwrap().is_empty());
+    }
+}
@@ -0,0 +1,135 @@
+use crate::download_to_path_with_options;
+use crate::DownloadOutcome;
+use crate::DownloadToPathOptions;
+use anyhow::Context;
+use std::collections::HashMap;
+use std::path::Path;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::sync::Mutex;
+
+/// A cache of downloaded files, by url.
+///
+/// Files are downloaded into a directory with [`download_to_path_with_options`],
+/// named by a hash of their url.
+/// The cache only remembers where files were downloaded to, so it starts out empty on each run.
+/// Concurrent fetches of the same uncached url both download it, so callers that fetch the same url at once should share the result instead.
+#[derive(Debug, Clone)]
+pub struct DownloadCache {
+    /// The client
+    client: reqwest::Client,
+
+    /// The paths of downloaded files, by url.
+    paths: Arc<Mutex<HashMap<String, PathBuf>>>,
+}
+
+impl DownloadCache {
+    /// Make a new, empty [`DownloadCache`] that downloads with the given client.
+    pub fn new(client: reqwest::Client) -> Self {
+        Self {
+            client,
+            paths: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    /// Get the path of the file at a url, downloading it into the given directory if it is not cached.
+    ///
+    /// A cached path is only used if the file still exists.
+    /// Otherwise, the file is downloaded to `dir` with the given options and added to the cache,
+    /// with a file name made from a hash of the url.
+    /// The directory must already exist.
+    pub async fn get_or_fetch(
+        &self,
+        url: &str,
+        dir: &Path,
+        options: DownloadToPathOptions,
+    ) -> anyhow::Result<PathBuf> {
+        let cached = self
+            .paths
+            .lock()
+            .unwrap_or_else(|error| error.into_inner())
+            .get(url)
+            .cloned();
+        if let Some(path) = cached {
+            if tokio::fs::try_exists(&path)
+                .await
+                .context("failed to check if file exists")?
+            {
+                return Ok(path);
+            }
+        }
+
+        let path = dir.join(cache_file_name(url));
+        let outcome = download_to_path_with_options(&self.client, url, &path, options)
+            .await
+            .with_context(|| format!("failed to download \"{url}\""))?;
+
+        // The server may have chosen the file name.
+        let path = match outcome {
+            DownloadOutcome::Downloaded { path, .. } => path,
+            DownloadOutcome::Unchanged | DownloadOutcome::Skipped => path,
+        };
+
+        self.paths
+            .lock()
+            .unwrap_or_else(|error| error.into_inner())
+            .insert(url.into(), path.clone());
+
+        Ok(path)
+    }
+}
+
+/// Get the name of the cache file for a url.
+///
+/// This uses the 64 bit FNV-1a hash, as it is stable across runs and platforms, unlike std's hashers.
+fn cache_file_name(url: &str) -> String {
+    const OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+    const PRIME: u64 = 0x0000_0100_0000_01b3;
+
+    let hash = url.bytes().fold(OFFSET_BASIS, |hash, byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(PRIME)
+    });
+
+    format!("{hash:016x}")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_server::response;
+    use crate::test_server::TestServer;
+
+    #[test]
+    fn cache_file_name_is_stable() {
+        assert!(cache_file_name("") == "cbf29ce484222325");
+        assert!(cache_file_name("a") == "af63dc4c8601ec8c");
+        assert!(cache_file_name("a") != cache_file_name("b"));
+    }
+
+    #[tokio::test]
+    async fn get_or_fetch_caches() {
+        let dir = Path::new("test_tmp/download_cache");
+        tokio::fs::create_dir_all(dir)
+            .await
+            .expect("failed to create tmp dir");
+
+        // This server can only answer one request.
+        let server = TestServer::spawn([response("200 OK", &[], b"data")]);
+        let url = format!("{}/file", server.url);
+
+        let cache = DownloadCache::new(reqwest::Client::new());
+        let path = cache
+            .get_or_fetch(&url, dir, DownloadToPathOptions::new())
+            .await
+            .expect("failed to fetch");
+        assert!(std::fs::read(&path).expect("failed to read") == b"data");
+
+        let cached_path = cache
+            .get_or_fetch(&url, dir, DownloadToPathOptions::new())
+            .await
+            .expect("failed to fetch cached");
+        assert!(cached_path == path);
+
+        assert!(server.join().len() == 1);
+    }
+}
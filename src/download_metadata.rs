@@ -0,0 +1,94 @@
+use std::time::SystemTime;
+
+/// Metadata about a download, written next to the downloaded file.
+///
+/// See [`DownloadToPathOptions::write_metadata`](crate::DownloadToPathOptions::write_metadata).
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct DownloadMetadata {
+    /// The url the file was downloaded from.
+    pub url: String,
+
+    /// The time the download finished.
+    ///
+    /// This is stored as an RFC 3339 timestamp, like "2015-10-21T07:28:00Z".
+    #[serde(with = "rfc3339")]
+    pub downloaded_at: SystemTime,
+
+    /// The size of the downloaded file, in bytes.
+    pub content_length: u64,
+
+    /// The `Content-Type` header of the response, if the server sent one.
+    pub content_type: Option<String>,
+
+    /// The `ETag` header of the response, if the server sent one.
+    pub etag: Option<String>,
+}
+
+impl DownloadMetadata {
+    /// Make a new [`DownloadMetadata`] for a download that just finished, using the headers of its response.
+    pub(crate) fn from_headers(
+        url: &str,
+        headers: &reqwest::header::HeaderMap,
+        content_length: u64,
+    ) -> Self {
+        let get_header = |name| {
+            headers
+                .get(name)
+                .and_then(|value: &reqwest::header::HeaderValue| value.to_str().ok())
+                .map(String::from)
+        };
+
+        Self {
+            url: url.into(),
+            downloaded_at: SystemTime::now(),
+            content_length,
+            content_type: get_header(reqwest::header::CONTENT_TYPE),
+            etag: get_header(reqwest::header::ETAG),
+        }
+    }
+}
+
+/// (De)serialize a [`SystemTime`] as an RFC 3339 timestamp.
+mod rfc3339 {
+    use serde::Deserialize;
+    use std::time::SystemTime;
+
+    pub fn serialize<S>(time: &SystemTime, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serializer.collect_str(&humantime::format_rfc3339(*time))
+    }
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<SystemTime, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        let time = String::deserialize(deserializer)?;
+        humantime::parse_rfc3339(&time).map_err(serde::de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn serde_round_trip() {
+        let metadata = DownloadMetadata {
+            url: "http://example.com/file.txt".into(),
+            downloaded_at: SystemTime::UNIX_EPOCH + Duration::from_secs(1445412480),
+            content_length: 4,
+            content_type: Some("text/plain".into()),
+            etag: None,
+        };
+
+        let json = serde_json::to_string(&metadata).expect("failed to serialize");
+        assert!(json.contains("\"downloaded_at\":\"2015-10-21T07:28:00Z\""));
+
+        let round_trip: DownloadMetadata =
+            serde_json::from_str(&json).expect("failed to deserialize");
+        assert!(round_trip == metadata);
+    }
+}
@@ -1,13 +1,324 @@
+use anyhow::bail;
 use anyhow::ensure;
 use anyhow::Context;
+use reqwest::header::ACCEPT_ENCODING;
+use reqwest::header::CONTENT_RANGE;
+use reqwest::header::CONTENT_TYPE;
+use reqwest::header::RANGE;
+use reqwest::header::RETRY_AFTER;
+use reqwest::StatusCode;
+use std::future::Future;
+use std::io::SeekFrom;
+use std::pin::Pin;
+use std::sync::atomic::AtomicU32;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::time::Duration;
+use std::time::SystemTime;
 use tokio::fs::File;
+use tokio::io::AsyncSeekExt;
+use tokio::io::AsyncWrite;
 use tokio::io::AsyncWriteExt;
+use tokio::sync::mpsc;
+
+/// An update on the progress of a download.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DownloadProgress {
+    /// The number of bytes downloaded so far.
+    pub downloaded: u64,
+
+    /// The number of bytes that the server said it would send, if it said.
+    pub total: Option<u64>,
+}
+
+/// Statistics about a finished download, from [`download_to_file_with_stats`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DownloadStats {
+    /// The number of bytes written to the file.
+    pub bytes: u64,
+
+    /// The number of redirects that were followed.
+    pub redirects: u32,
+}
+
+/// A function that is called with each chunk of a download.
+type InspectFn = dyn FnMut(&[u8]) + Send;
+
+/// A function that is called after each chunk of a download is written,
+/// with the writer and the number of bytes written so far.
+///
+/// The returned future is awaited before the next chunk is read.
+pub(crate) type AfterChunkFn<'f, W> =
+    dyn for<'a> FnMut(&'a mut W, u64) -> AfterChunkFuture<'a> + Send + 'f;
+
+/// The future returned by an [`AfterChunkFn`], which may borrow the writer.
+pub(crate) type AfterChunkFuture<'a> =
+    Pin<Box<dyn Future<Output = anyhow::Result<()>> + Send + 'a>>;
+
+/// Options for [`download_to_file_with_options`].
+pub struct DownloadToFileOptions {
+    /// The function to call with each chunk before it is written.
+    inspect: Option<Box<InspectFn>>,
+
+    /// Whether the server may send a compressed body.
+    decompress: bool,
+
+    /// The longest to wait before retrying a request that the server said to retry later.
+    retry_after_max_wait: Option<Duration>,
+
+    /// The redirect policy to use instead of the client's.
+    redirect_policy: Option<reqwest::redirect::Policy>,
+}
+
+impl DownloadToFileOptions {
+    /// Make a new [`DownloadToFileOptions`] with the default settings.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Call the given function with each chunk of the body, before it is written to the file.
+    ///
+    /// This is useful for observing the bytes of a download, like in tests.
+    /// Chunks are passed in order, and the function is not called for a chunk that failed to arrive.
+    pub fn inspect<F>(mut self, inspect: F) -> Self
+    where
+        F: FnMut(&[u8]) + Send + 'static,
+    {
+        self.inspect = Some(Box::new(inspect));
+        self
+    }
+
+    /// Set whether the server may send a compressed body, which is decompressed before it is written.
+    ///
+    /// Decompression is done by `reqwest`, so it only happens if the client supports it.
+    /// The `decompress` feature enables `gzip` and `brotli` support for all clients,
+    /// which then send an `Accept-Encoding` header with every request.
+    /// If false, an `Accept-Encoding: identity` header is sent instead, asking the server not to compress the body.
+    ///
+    /// This can't turn decompression off for the client, as that is set when the client is built.
+    /// If the server compresses the body anyway, a client with `gzip` or `brotli` support still decompresses it.
+    /// Since the feature applies to every client in the process,
+    /// this also includes requests made outside of these options, like the `Range` requests of [`download_resume`].
+    /// To never decompress, build the client with [`reqwest::ClientBuilder::no_gzip`]
+    /// and [`reqwest::ClientBuilder::no_brotli`].
+    ///
+    /// Defaults to true.
+    pub fn decompress(mut self, decompress: bool) -> Self {
+        self.decompress = decompress;
+        self
+    }
+
+    /// Retry the request if the server responds with `429 Too Many Requests` and a `Retry-After` header.
+    ///
+    /// Before retrying, this waits for as long as the server asked, up to `max_wait`.
+    /// The request is retried at most 3 times.
+    /// If the server keeps responding with 429, or does not send a valid `Retry-After` header, an error is returned.
+    ///
+    /// Defaults to not retrying.
+    pub fn respect_retry_after(mut self, max_wait: Duration) -> Self {
+        self.retry_after_max_wait = Some(max_wait);
+        self
+    }
+
+    /// Use the given redirect policy instead of the client's.
+    ///
+    /// This can be used to disallow redirects, limit them, or only follow redirects to certain domains.
+    /// Since the redirect policy belongs to the client,
+    /// it is only used by [`download_to_file_with_client_builder`], which builds the client itself.
+    /// [`download_to_file_with_options`] returns an error if this is set.
+    ///
+    /// Defaults to the client's policy.
+    pub fn redirect_policy(mut self, policy: reqwest::redirect::Policy) -> Self {
+        self.redirect_policy = Some(policy);
+        self
+    }
+}
+
+impl Default for DownloadToFileOptions {
+    fn default() -> Self {
+        Self {
+            inspect: None,
+            decompress: true,
+            retry_after_max_wait: None,
+            redirect_policy: None,
+        }
+    }
+}
+
+impl std::fmt::Debug for DownloadToFileOptions {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("DownloadToFileOptions")
+            .field("inspect", &self.inspect.as_ref().map(|_| "..."))
+            .field("decompress", &self.decompress)
+            .field("retry_after_max_wait", &self.retry_after_max_wait)
+            .field("redirect_policy", &self.redirect_policy)
+            .finish()
+    }
+}
 
 /// Download a url using a GET request to a tokio file.
 pub async fn download_to_file(
     client: &reqwest::Client,
     url: &str,
     file: &mut File,
+) -> anyhow::Result<()> {
+    // Send the request
+    let response = client
+        .get(url)
+        .send()
+        .await
+        .context("failed to get headers")?
+        .error_for_status()?;
+
+    write_response_to_file(response, file, None, None, None, None).await?;
+
+    Ok(())
+}
+
+/// Download a url using a GET request to a tokio file, with the given options.
+///
+/// This is like [`download_to_file`], with the extra behavior described by [`DownloadToFileOptions`].
+///
+/// # Errors
+/// Returns an error if the options have a redirect policy,
+/// as it can't be applied to an existing client.
+/// Use [`download_to_file_with_client_builder`] instead.
+pub async fn download_to_file_with_options(
+    client: &reqwest::Client,
+    url: &str,
+    file: &mut File,
+    options: DownloadToFileOptions,
+) -> anyhow::Result<()> {
+    ensure!(
+        options.redirect_policy.is_none(),
+        "a redirect policy can't be applied to an existing client, use download_to_file_with_client_builder"
+    );
+
+    download_to_file_with_client(client, url, file, options).await
+}
+
+/// Download a url using a GET request to a tokio file, with the given options, building the client from a builder.
+///
+/// Since the redirect policy belongs to the client, a client is built from the given builder,
+/// with the redirect policy of the options if one was set.
+/// Otherwise, this is the same as [`download_to_file_with_options`].
+pub async fn download_to_file_with_client_builder(
+    mut client_builder: reqwest::ClientBuilder,
+    url: &str,
+    file: &mut File,
+    mut options: DownloadToFileOptions,
+) -> anyhow::Result<()> {
+    if let Some(policy) = options.redirect_policy.take() {
+        client_builder = client_builder.redirect(policy);
+    }
+    let client = client_builder.build().context("failed to build client")?;
+
+    download_to_file_with_client(&client, url, file, options).await
+}
+
+/// Download a url to a tokio file with the given client and options, ignoring any redirect policy of the options.
+async fn download_to_file_with_client(
+    client: &reqwest::Client,
+    url: &str,
+    file: &mut File,
+    mut options: DownloadToFileOptions,
+) -> anyhow::Result<()> {
+    let mut retries = 0;
+    let response = loop {
+        let mut request = client.get(url);
+        if !options.decompress {
+            request = request.header(ACCEPT_ENCODING, "identity");
+        }
+
+        // Send the request
+        let response = request.send().await.context("failed to get headers")?;
+
+        // Wait and retry if the server asked us to.
+        if let Some(max_wait) = options.retry_after_max_wait {
+            if response.status() == StatusCode::TOO_MANY_REQUESTS
+                && retries < MAX_RETRY_AFTER_RETRIES
+            {
+                if let Some(wait) = parse_retry_after(response.headers()) {
+                    retries += 1;
+                    tokio::time::sleep(wait.min(max_wait)).await;
+                    continue;
+                }
+            }
+        }
+
+        break response.error_for_status()?;
+    };
+
+    write_response_to_file(
+        response,
+        file,
+        None,
+        None,
+        options.inspect.as_deref_mut(),
+        None,
+    )
+    .await?;
+
+    Ok(())
+}
+
+/// The most times [`download_to_file_with_options`] retries a request for a `Retry-After` header.
+const MAX_RETRY_AFTER_RETRIES: u32 = 3;
+
+/// Parse the `Retry-After` header of a response, which is either a number of seconds or an HTTP date.
+///
+/// A date in the past gives a zero duration.
+fn parse_retry_after(headers: &reqwest::header::HeaderMap) -> Option<Duration> {
+    let retry_after = headers.get(RETRY_AFTER)?.to_str().ok()?.trim();
+    if let Ok(seconds) = retry_after.parse() {
+        return Some(Duration::from_secs(seconds));
+    }
+
+    let date = httpdate::parse_http_date(retry_after).ok()?;
+    Some(
+        date.duration_since(SystemTime::now())
+            .unwrap_or(Duration::ZERO),
+    )
+}
+
+/// Download a url using a GET request to a tokio writer.
+///
+/// Unlike [`download_to_file`], nothing is preallocated or synced,
+/// though the writer is flushed once the download completes.
+/// The number of bytes received is still checked against the `Content-Length` header, if the server sent one.
+/// Since the bytes are written as they arrive, the writer may have received a partial body when this fails.
+pub async fn download_to_writer<W>(
+    client: &reqwest::Client,
+    url: &str,
+    mut writer: W,
+) -> anyhow::Result<()>
+where
+    W: AsyncWrite + Unpin,
+{
+    // Send the request
+    let response = client
+        .get(url)
+        .send()
+        .await
+        .context("failed to get headers")?
+        .error_for_status()?;
+
+    write_response_to_writer(response, &mut writer, None, None, None, None).await?;
+
+    Ok(())
+}
+
+/// Download a url using a GET request, sending each chunk of the body to a channel.
+///
+/// This lets the receiver process chunks while more are being downloaded.
+/// Since the channel is bounded, a slow receiver will slow down the download instead of buffering the whole body.
+/// The number of bytes received is checked against the `Content-Length` header, if the server sent one,
+/// though chunks that were already sent cannot be taken back if this fails.
+/// If the receiver is dropped, the download stops and an error is returned.
+pub async fn download_to_channel(
+    client: &reqwest::Client,
+    url: &str,
+    tx: tokio::sync::mpsc::Sender<bytes::Bytes>,
 ) -> anyhow::Result<()> {
     // Send the request
     let mut response = client
@@ -17,26 +328,501 @@ pub async fn download_to_file(
         .context("failed to get headers")?
         .error_for_status()?;
 
-    // Pre-allocate file space if possible.
     let content_length = response.content_length();
+    let mut actual_length = 0;
+    while let Some(chunk) = response.chunk().await.context("failed to get next chunk")? {
+        // This will panic if the server sends back a chunk larger than u64::MAX,
+        // which is incredibly unlikely/impossible.
+        actual_length += u64::try_from(chunk.len()).unwrap();
+
+        tx.send(chunk).await.context("channel closed")?;
+    }
+
+    // Ensure size matches content_length
     if let Some(content_length) = content_length {
-        file.set_len(content_length)
+        ensure!(
+            content_length == actual_length,
+            "content-length mismatch, {content_length} (content length) != {actual_length} (actual length)",
+        );
+    }
+
+    Ok(())
+}
+
+/// Download a url using a GET request to a tokio file, ensuring that the response has the given MIME type.
+///
+/// The MIME type of the `Content-Type` header is compared without its parameters, ignoring case.
+/// For example, an `expected_mime` of "text/html" matches "text/html; charset=utf-8".
+/// If the types do not match or the header is missing, nothing is written to the file and an error is returned.
+pub async fn download_to_file_typed(
+    client: &reqwest::Client,
+    url: &str,
+    file: &mut File,
+    expected_mime: &str,
+) -> anyhow::Result<()> {
+    // Send the request
+    let response = client
+        .get(url)
+        .send()
+        .await
+        .context("failed to get headers")?
+        .error_for_status()?;
+
+    // Check the type before writing anything.
+    let mime_type = response_mime_type(&response).context("missing content-type header")?;
+    ensure!(
+        mime_type.eq_ignore_ascii_case(expected_mime),
+        "content-type mismatch, {mime_type} (actual) != {expected_mime} (expected)"
+    );
+
+    write_response_to_file(response, file, None, None, None, None).await?;
+
+    Ok(())
+}
+
+/// Get the MIME type of a response from its `Content-Type` header, without any parameters.
+///
+/// # Returns
+/// Returns `None` if the header is missing or is not valid UTF-8.
+pub(crate) fn response_mime_type(response: &reqwest::Response) -> Option<&str> {
+    let content_type = response.headers().get(CONTENT_TYPE)?.to_str().ok()?;
+    let mime_type = content_type
+        .split_once(';')
+        .map_or(content_type, |(mime_type, _)| mime_type);
+
+    Some(mime_type.trim())
+}
+
+/// Resume downloading a url to a tokio file, using a GET request with a `Range` header.
+///
+/// The current length of the file is taken to be the number of bytes that were already downloaded.
+/// If the server responds with `206 Partial Content`, the remaining bytes are appended to the file.
+/// If the server responds with `200 OK` instead, the file is truncated and the whole file is downloaded.
+/// If the server reports that the file is already complete, nothing is written.
+pub async fn download_resume(
+    client: &reqwest::Client,
+    url: &str,
+    file: &mut File,
+) -> anyhow::Result<()> {
+    let start = file
+        .metadata()
+        .await
+        .context("failed to get file metadata")?
+        .len();
+
+    // Send the request
+    let response = client
+        .get(url)
+        .header(RANGE, format!("bytes={start}-"))
+        .send()
+        .await
+        .context("failed to get headers")?;
+
+    // The server sends this if we ask for bytes past the end of the file.
+    // If our file is exactly as long as the server's, it is already complete.
+    if response.status() == StatusCode::RANGE_NOT_SATISFIABLE {
+        let (_, total) = parse_content_range(&response)?;
+        if total == Some(start) {
+            return Ok(());
+        }
+    }
+    let response = response.error_for_status()?;
+
+    if response.status() == StatusCode::PARTIAL_CONTENT {
+        // Make sure the server is sending the part that we asked for.
+        let (range_start, _) = parse_content_range(&response)?;
+        ensure!(
+            range_start == Some(start),
+            "server sent a range that does not start at {start}"
+        );
+
+        file.seek(SeekFrom::Start(start))
+            .await
+            .context("failed to seek to end of file")?;
+    } else {
+        // The server ignored our range, so start over.
+        file.seek(SeekFrom::Start(0))
+            .await
+            .context("failed to seek to start of file")?;
+        file.set_len(0).await.context("failed to truncate file")?;
+    }
+
+    write_response_to_file(response, file, None, None, None, None).await?;
+
+    Ok(())
+}
+
+/// Parse the `Content-Range` header of a response.
+///
+/// # Returns
+/// Returns the start of the range and the total length.
+/// Either will be `None` if the server did not send it.
+pub(crate) fn parse_content_range(
+    response: &reqwest::Response,
+) -> anyhow::Result<(Option<u64>, Option<u64>)> {
+    let Some(content_range) = response.headers().get(CONTENT_RANGE) else {
+        bail!("missing content-range header");
+    };
+    let content_range = content_range
+        .to_str()
+        .context("invalid content-range header")?;
+
+    // Looks like "bytes 0-99/100", "bytes 0-99/*", or "bytes */100".
+    let Some((range, total)) = content_range
+        .strip_prefix("bytes ")
+        .and_then(|content_range| content_range.split_once('/'))
+    else {
+        bail!("invalid content-range header \"{content_range}\"");
+    };
+    let start = match range {
+        "*" => None,
+        range => {
+            let (start, _) = range
+                .split_once('-')
+                .with_context(|| format!("invalid content-range header \"{content_range}\""))?;
+            Some(start.parse().context("invalid content-range start")?)
+        }
+    };
+    let total = match total {
+        "*" => None,
+        total => Some(total.parse().context("invalid content-range length")?),
+    };
+
+    Ok((start, total))
+}
+
+/// Download a url using a GET request to a tokio file, following at most `max_redirects` redirects.
+///
+/// Since the redirect policy belongs to the client, a client is built from the given builder with a limited
+/// [`reqwest::redirect::Policy`].
+/// If the server redirects more than `max_redirects` times, nothing is written to the file and an error is returned.
+pub async fn download_to_file_limited_redirects(
+    client_builder: reqwest::ClientBuilder,
+    url: &str,
+    file: &mut File,
+    max_redirects: u32,
+) -> anyhow::Result<()> {
+    // reqwest counts the original url against the limit,
+    // so it needs to be 1 higher to allow `max_redirects` redirects.
+    let limit = usize::try_from(max_redirects)
+        .context("max redirects is too large")?
+        .saturating_add(1);
+    let client = client_builder
+        .redirect(reqwest::redirect::Policy::limited(limit))
+        .build()
+        .context("failed to build client")?;
+
+    download_to_file(&client, url, file).await
+}
+
+/// The most redirects that [`download_to_file_with_stats`] follows.
+const MAX_COUNTED_REDIRECTS: usize = 10;
+
+/// Download a url using a GET request to a tokio file, reporting statistics about the download.
+///
+/// This is useful for noticing misconfigured mirrors or redirect loops.
+/// Since the redirect policy belongs to the client, a client is built from the given builder
+/// with a policy that counts redirects.
+/// At most 10 redirects are followed, after which an error is returned.
+pub async fn download_to_file_with_stats(
+    client_builder: reqwest::ClientBuilder,
+    url: &str,
+    file: &mut File,
+) -> anyhow::Result<DownloadStats> {
+    // The client is only used for this download, so the counter only counts its redirects.
+    let redirects = Arc::new(AtomicU32::new(0));
+    let policy = reqwest::redirect::Policy::custom({
+        let redirects = redirects.clone();
+        move |attempt| {
+            // The original url is the first of the previous urls.
+            if attempt.previous().len() > MAX_COUNTED_REDIRECTS {
+                return attempt.error("too many redirects");
+            }
+
+            redirects.fetch_add(1, Ordering::Relaxed);
+            attempt.follow()
+        }
+    });
+    let client = client_builder
+        .redirect(policy)
+        .build()
+        .context("failed to build client")?;
+
+    // Send the request
+    let response = client
+        .get(url)
+        .send()
+        .await
+        .context("failed to get headers")?
+        .error_for_status()?;
+
+    let bytes = write_response_to_file(response, file, None, None, None, None).await?;
+
+    Ok(DownloadStats {
+        bytes,
+        redirects: redirects.load(Ordering::Relaxed),
+    })
+}
+
+/// Download a url using a GET request to a tokio file, ensuring that the body is JSON that matches a JSON schema.
+///
+/// The whole body is downloaded and validated before anything is written to the file.
+/// If the body does not match the schema, nothing is written and a [`JsonValidationError`] is returned,
+/// which may be recovered with [`anyhow::Error::downcast_ref`].
+#[cfg(feature = "jsonschema")]
+pub async fn download_json_validated(
+    client: &reqwest::Client,
+    url: &str,
+    file: &mut File,
+    schema: &serde_json::Value,
+) -> anyhow::Result<()> {
+    let validator = jsonschema::validator_for(schema).context("invalid json schema")?;
+
+    // Send the request
+    let response = client
+        .get(url)
+        .send()
+        .await
+        .context("failed to get headers")?
+        .error_for_status()?;
+    let body = response.bytes().await.context("failed to get body")?;
+
+    // Validate before writing anything.
+    let value: serde_json::Value = serde_json::from_slice(&body).context("invalid json")?;
+    let errors: Vec<_> = validator
+        .iter_errors(&value)
+        .map(|error| error.to_string())
+        .collect();
+    if !errors.is_empty() {
+        return Err(JsonValidationError { errors }.into());
+    }
+
+    file.write_all(&body)
+        .await
+        .context("failed to write to file")?;
+
+    // Sync data
+    file.flush().await.context("failed to flush file")?;
+    file.sync_all().await.context("failed to sync file data")?;
+
+    Ok(())
+}
+
+/// An error for a JSON document that does not match a schema.
+#[cfg(feature = "jsonschema")]
+#[derive(Debug)]
+pub struct JsonValidationError {
+    /// The messages of each validation error.
+    errors: Vec<String>,
+}
+
+#[cfg(feature = "jsonschema")]
+impl JsonValidationError {
+    /// Get the messages of each validation error.
+    ///
+    /// This is never empty.
+    pub fn errors(&self) -> &[String] {
+        &self.errors
+    }
+}
+
+#[cfg(feature = "jsonschema")]
+impl std::fmt::Display for JsonValidationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "json does not match the schema: {}",
+            self.errors.join("; ")
+        )
+    }
+}
+
+#[cfg(feature = "jsonschema")]
+impl std::error::Error for JsonValidationError {}
+
+/// Download a url using a GET request to a tokio file, pinning the server's certificate.
+///
+/// A client is built from the given builder,
+/// with `expected_cert_der` added as a root certificate so that self-signed certificates can be pinned.
+/// A builder is taken instead of a client, since root certificates and TLS info can only be configured on a builder.
+/// After connecting, the server's leaf certificate is compared against `expected_cert_der`.
+/// If they differ, or the connection did not use TLS, nothing is written to the file and an error is returned.
+///
+/// Note that only the connection the file is downloaded from is checked,
+/// so any followed redirects must also be served with the pinned certificate.
+#[cfg(feature = "download-to-file-pinned")]
+pub async fn download_to_file_pinned(
+    client_builder: reqwest::ClientBuilder,
+    url: &str,
+    file: &mut File,
+    expected_cert_der: &[u8],
+) -> anyhow::Result<()> {
+    // Build a client that trusts the pinned certificate and reports the peer certificate.
+    let certificate =
+        reqwest::Certificate::from_der(expected_cert_der).context("invalid pinned certificate")?;
+    let client = client_builder
+        .add_root_certificate(certificate)
+        .tls_info(true)
+        .build()
+        .context("failed to build client")?;
+
+    // Send the request
+    let response = client
+        .get(url)
+        .send()
+        .await
+        .context("failed to get headers")?
+        .error_for_status()?;
+
+    // Check the leaf certificate before writing anything.
+    let peer_certificate = response
+        .extensions()
+        .get::<reqwest::tls::TlsInfo>()
+        .and_then(|tls_info| tls_info.peer_certificate())
+        .context("the response was not sent over TLS")?;
+    ensure!(
+        peer_certificate == expected_cert_der,
+        "the server certificate does not match the pinned certificate"
+    );
+
+    write_response_to_file(response, file, None, None, None, None).await?;
+
+    Ok(())
+}
+
+/// Write the body of a response to a tokio file, starting at the current position of the file.
+///
+/// If a read timeout is given, waiting longer than it for any one chunk is an error.
+/// If a progress channel is given, an update is sent to it after each chunk is written.
+/// If an inspect function is given, it is called with each chunk before it is written.
+/// If an after chunk function is given, it is called after each chunk is written.
+///
+/// # Returns
+/// Returns the number of bytes written.
+pub(crate) async fn write_response_to_file(
+    response: reqwest::Response,
+    file: &mut File,
+    read_timeout: Option<Duration>,
+    progress: Option<&mpsc::Sender<DownloadProgress>>,
+    inspect: Option<&mut InspectFn>,
+    after_chunk: Option<&mut AfterChunkFn<'_, File>>,
+) -> anyhow::Result<u64> {
+    // Pre-allocate file space if possible.
+    if let Some(content_length) = response.content_length() {
+        let position = file
+            .stream_position()
+            .await
+            .context("failed to get file position")?;
+        file.set_len(position + content_length)
             .await
             .context("failed to pre-allocate file")?;
     }
 
+    let actual_length =
+        write_response_to_writer(response, file, read_timeout, progress, inspect, after_chunk)
+            .await?;
+
+    // Sync data
+    file.sync_all().await.context("failed to sync file data")?;
+
+    Ok(actual_length)
+}
+
+/// Write the body of a response to a tokio file like [`write_response_to_file`], advancing a progress bar as it goes.
+///
+/// The length of the progress bar is left to the caller.
+#[cfg(feature = "progress")]
+pub(crate) async fn write_response_to_file_with_progress(
+    response: reqwest::Response,
+    file: &mut File,
+    read_timeout: Option<Duration>,
+    progress: Option<&mpsc::Sender<DownloadProgress>>,
+    progress_bar: &indicatif::ProgressBar,
+) -> anyhow::Result<u64> {
+    // Pre-allocate file space if possible.
+    if let Some(content_length) = response.content_length() {
+        let position = file
+            .stream_position()
+            .await
+            .context("failed to get file position")?;
+        file.set_len(position + content_length)
+            .await
+            .context("failed to pre-allocate file")?;
+    }
+
+    let mut writer = progress_bar.wrap_async_write(&mut *file);
+    let actual_length =
+        write_response_to_writer(response, &mut writer, read_timeout, progress, None, None).await?;
+
+    // Sync data
+    file.sync_all().await.context("failed to sync file data")?;
+
+    Ok(actual_length)
+}
+
+/// Write the body of a response to a tokio writer, flushing it once done.
+///
+/// If a read timeout is given, waiting longer than it for any one chunk is an error.
+/// If a progress channel is given, an update is sent to it after each chunk is written.
+/// A closed channel is not an error, as the receiver may simply have stopped listening.
+/// If an inspect function is given, it is called with each chunk before it is written.
+/// If an after chunk function is given, it is called after each chunk is written and its progress is sent.
+///
+/// # Returns
+/// Returns the number of bytes written.
+async fn write_response_to_writer<W>(
+    mut response: reqwest::Response,
+    writer: &mut W,
+    read_timeout: Option<Duration>,
+    progress: Option<&mpsc::Sender<DownloadProgress>>,
+    mut inspect: Option<&mut InspectFn>,
+    mut after_chunk: Option<&mut AfterChunkFn<'_, W>>,
+) -> anyhow::Result<u64>
+where
+    W: AsyncWrite + Unpin,
+{
+    let content_length = response.content_length();
+
     // Keep track of the file size in case the server lies
     let mut actual_length = 0;
 
     // Download the file chunk-by-chunk
-    while let Some(chunk) = response.chunk().await.context("failed to get next chunk")? {
-        file.write_all(&chunk)
+    loop {
+        let chunk = match read_timeout {
+            Some(read_timeout) => tokio::time::timeout(read_timeout, response.chunk())
+                .await
+                .context("timed out getting next chunk")?,
+            None => response.chunk().await,
+        };
+        let Some(chunk) = chunk.context("failed to get next chunk")? else {
+            break;
+        };
+
+        if let Some(inspect) = inspect.as_mut() {
+            inspect(&chunk);
+        }
+
+        writer
+            .write_all(&chunk)
             .await
-            .context("failed to write to file")?;
+            .context("failed to write to writer")?;
 
         // This will panic if the server sends back a chunk larger than u64::MAX,
         // which is incredibly unlikely/impossible.
         actual_length += u64::try_from(chunk.len()).unwrap();
+
+        if let Some(progress) = progress {
+            let _ = progress
+                .send(DownloadProgress {
+                    downloaded: actual_length,
+                    total: content_length,
+                })
+                .await;
+        }
+
+        if let Some(after_chunk) = after_chunk.as_mut() {
+            after_chunk(writer, actual_length).await?;
+        }
     }
 
     // Ensure file size matches content_length
@@ -47,16 +833,26 @@ pub async fn download_to_file(
         );
     }
 
-    // Sync data
-    file.flush().await.context("failed to flush file")?;
-    file.sync_all().await.context("failed to sync file data")?;
+    writer.flush().await.context("failed to flush writer")?;
 
-    Ok(())
+    Ok(actual_length)
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::test_server::response;
+    use crate::test_server::TestServer;
+
+    /// A self-signed certificate for pinning tests.
+    #[cfg(feature = "download-to-file-pinned")]
+    const PINNED_CERT: &[u8] = include_bytes!("../test_data/pinned_cert.der");
+    #[cfg(feature = "download-to-file-pinned")]
+    const PINNED_KEY: &[u8] = include_bytes!("../test_data/pinned_key.der");
+    #[cfg(feature = "download-to-file-pinned")]
+    const OTHER_CERT: &[u8] = include_bytes!("../test_data/other_cert.der");
+    #[cfg(feature = "download-to-file-pinned")]
+    const OTHER_KEY: &[u8] = include_bytes!("../test_data/other_key.der");
 
     #[tokio::test]
     async fn it_works() {
@@ -71,4 +867,480 @@ mod test {
             .await
             .expect("failed to download");
     }
+
+    #[tokio::test]
+    async fn typed_works() {
+        tokio::fs::create_dir_all("test_tmp")
+            .await
+            .expect("failed to create tmp dir");
+        let path = "test_tmp/download_to_file_typed.txt";
+
+        let server = TestServer::spawn([
+            response(
+                "200 OK",
+                &["Content-Type: Text/Plain; charset=utf-8"],
+                b"data",
+            ),
+            response("200 OK", &["Content-Type: text/html"], b"<html>"),
+        ]);
+        let url = format!("{}/file", server.url);
+        let client = reqwest::Client::new();
+
+        let mut file = File::create(path).await.expect("failed to open");
+        download_to_file_typed(&client, &url, &mut file, "text/plain")
+            .await
+            .expect("failed to download");
+        drop(file);
+        let data = tokio::fs::read(path).await.expect("failed to read");
+        assert!(data == b"data");
+
+        let mut file = File::create(path).await.expect("failed to open");
+        download_to_file_typed(&client, &url, &mut file, "text/plain")
+            .await
+            .expect_err("downloaded wrong type");
+        drop(file);
+        let data = tokio::fs::read(path).await.expect("failed to read");
+        assert!(data.is_empty());
+
+        server.join();
+    }
+
+    #[tokio::test]
+    async fn inspect_works() {
+        tokio::fs::create_dir_all("test_tmp")
+            .await
+            .expect("failed to create tmp dir");
+        let path = "test_tmp/download_to_file_inspect.txt";
+
+        let server = TestServer::spawn([response("200 OK", &[], b"data")]);
+        let url = format!("{}/file", server.url);
+        let client = reqwest::Client::new();
+
+        let inspected = std::sync::Arc::new(std::sync::Mutex::new(Vec::<u8>::new()));
+        let options = DownloadToFileOptions::new().inspect({
+            let inspected = inspected.clone();
+            move |chunk: &[u8]| inspected.lock().unwrap().extend(chunk)
+        });
+        let mut file = File::create(path).await.expect("failed to open");
+        download_to_file_with_options(&client, &url, &mut file, options)
+            .await
+            .expect("failed to download");
+        drop(file);
+        assert!(*inspected.lock().unwrap() == b"data");
+        let data = tokio::fs::read(path).await.expect("failed to read");
+        assert!(data == b"data");
+
+        server.join();
+    }
+
+    #[tokio::test]
+    async fn decompress_works() {
+        tokio::fs::create_dir_all("test_tmp")
+            .await
+            .expect("failed to create tmp dir");
+        let path = "test_tmp/download_to_file_decompress.txt";
+
+        // "data", compressed with gzip.
+        let compressed = b"\x1f\x8b\x08\x00\x00\x00\x00\x00\x02\x03\x4b\x49\x2c\x49\x04\x00\x63\xf3\xf3\xad\x04\x00\x00\x00";
+        let server = TestServer::spawn([
+            response("200 OK", &["Content-Encoding: gzip"], compressed),
+            response("200 OK", &[], b"data"),
+        ]);
+        let url = format!("{}/file", server.url);
+        let client = reqwest::Client::new();
+
+        let mut file = File::create(path).await.expect("failed to open");
+        download_to_file_with_options(&client, &url, &mut file, DownloadToFileOptions::new())
+            .await
+            .expect("failed to download");
+        drop(file);
+        let data = tokio::fs::read(path).await.expect("failed to read");
+        if cfg!(feature = "decompress") {
+            assert!(data == b"data");
+        } else {
+            assert!(data == compressed);
+        }
+
+        let mut file = File::create(path).await.expect("failed to open");
+        let options = DownloadToFileOptions::new().decompress(false);
+        download_to_file_with_options(&client, &url, &mut file, options)
+            .await
+            .expect("failed to download");
+        drop(file);
+        let data = tokio::fs::read(path).await.expect("failed to read");
+        assert!(data == b"data");
+
+        let requests = server.join();
+        assert!(requests[1].contains("accept-encoding: identity"));
+    }
+
+    #[tokio::test]
+    async fn writer_works() {
+        let server = TestServer::spawn([
+            response("200 OK", &[], b"data"),
+            b"HTTP/1.1 200 OK\r\nContent-Length: 8\r\nConnection: close\r\n\r\ndata".to_vec(),
+        ]);
+        let url = format!("{}/file", server.url);
+        let client = reqwest::Client::new();
+
+        let mut data = Vec::new();
+        download_to_writer(&client, &url, &mut data)
+            .await
+            .expect("failed to download");
+        assert!(data == b"data");
+
+        // The server lies about the length here.
+        let mut data = Vec::new();
+        download_to_writer(&client, &url, &mut data)
+            .await
+            .expect_err("accepted short body");
+
+        server.join();
+    }
+
+    #[tokio::test]
+    async fn channel_works() {
+        let server = TestServer::spawn([response("200 OK", &[], b"data")]);
+        let url = format!("{}/file", server.url);
+        let client = reqwest::Client::new();
+
+        let (tx, mut rx) = tokio::sync::mpsc::channel(1);
+        let receiver = tokio::spawn(async move {
+            let mut data = Vec::new();
+            while let Some(chunk) = rx.recv().await {
+                data.extend(chunk);
+            }
+            data
+        });
+        download_to_channel(&client, &url, tx)
+            .await
+            .expect("failed to download");
+        let data = tokio::time::timeout(Duration::from_secs(5), receiver)
+            .await
+            .expect("timed out waiting for task")
+            .expect("failed to join task");
+        assert!(data == b"data");
+
+        server.join();
+    }
+
+    #[tokio::test]
+    async fn limited_redirects_works() {
+        tokio::fs::create_dir_all("test_tmp")
+            .await
+            .expect("failed to create tmp dir");
+        let path = "test_tmp/download_to_file_limited_redirects.txt";
+
+        let redirect = || response("302 Found", &["Location: /redirect"], b"");
+        let server = TestServer::spawn([
+            redirect(),
+            redirect(),
+            response("200 OK", &[], b"data"),
+            redirect(),
+            redirect(),
+        ]);
+        let url = format!("{}/file", server.url);
+
+        let mut file = File::create(path).await.expect("failed to open");
+        download_to_file_limited_redirects(reqwest::Client::builder(), &url, &mut file, 2)
+            .await
+            .expect("failed to download");
+        drop(file);
+        let data = tokio::fs::read(path).await.expect("failed to read");
+        assert!(data == b"data");
+
+        let mut file = File::create(path).await.expect("failed to open");
+        download_to_file_limited_redirects(reqwest::Client::builder(), &url, &mut file, 1)
+            .await
+            .expect_err("followed too many redirects");
+
+        server.join();
+    }
+
+    #[tokio::test]
+    async fn respect_retry_after_works() {
+        tokio::fs::create_dir_all("test_tmp")
+            .await
+            .expect("failed to create tmp dir");
+        let path = "test_tmp/download_to_file_respect_retry_after.txt";
+
+        let too_many_requests = || response("429 Too Many Requests", &["Retry-After: 0"], b"");
+        let server = TestServer::spawn([
+            too_many_requests(),
+            response("200 OK", &[], b"data"),
+            too_many_requests(),
+            too_many_requests(),
+            too_many_requests(),
+            too_many_requests(),
+        ]);
+        let url = format!("{}/file", server.url);
+
+        let client = reqwest::Client::new();
+        let options = || DownloadToFileOptions::new().respect_retry_after(Duration::from_secs(1));
+        let mut file = File::create(path).await.expect("failed to open");
+        download_to_file_with_options(&client, &url, &mut file, options())
+            .await
+            .expect("failed to download");
+        drop(file);
+        assert!(tokio::fs::read(path).await.expect("failed to read") == b"data");
+
+        // The server keeps asking us to retry later.
+        let mut file = File::create(path).await.expect("failed to open");
+        download_to_file_with_options(&client, &url, &mut file, options())
+            .await
+            .expect_err("downloaded after too many retries");
+
+        server.join();
+    }
+
+    #[tokio::test]
+    async fn redirect_policy_works() {
+        tokio::fs::create_dir_all("test_tmp")
+            .await
+            .expect("failed to create tmp dir");
+        let path = "test_tmp/download_to_file_redirect_policy.txt";
+
+        let redirect = || response("302 Found", &["Location: /redirect"], b"");
+        let server = TestServer::spawn([redirect(), response("200 OK", &[], b"data"), redirect()]);
+        let url = format!("{}/file", server.url);
+
+        let client = reqwest::Client::new();
+        let mut file = File::create(path).await.expect("failed to open");
+        download_to_file_with_options(&client, &url, &mut file, DownloadToFileOptions::new())
+            .await
+            .expect("failed to download");
+        drop(file);
+        assert!(tokio::fs::read(path).await.expect("failed to read") == b"data");
+
+        // This policy refuses every redirect.
+        let options = || {
+            DownloadToFileOptions::new().redirect_policy(reqwest::redirect::Policy::custom(
+                |attempt| attempt.error("redirects are not allowed"),
+            ))
+        };
+
+        // The policy can't be applied to an existing client.
+        let mut file = File::create(path).await.expect("failed to open");
+        download_to_file_with_options(&client, &url, &mut file, options())
+            .await
+            .expect_err("used a redirect policy with an existing client");
+
+        // The rest of the builder's settings are kept.
+        let mut headers = reqwest::header::HeaderMap::new();
+        headers.insert("x-test", reqwest::header::HeaderValue::from_static("1"));
+        let client_builder = reqwest::Client::builder().default_headers(headers);
+        download_to_file_with_client_builder(client_builder, &url, &mut file, options())
+            .await
+            .expect_err("followed a refused redirect");
+
+        let requests = server.join();
+        assert!(requests.len() == 3);
+        assert!(requests[2].contains("x-test: 1"));
+    }
+
+    #[cfg(feature = "download-to-file-pinned")]
+    #[tokio::test]
+    async fn pinned_accepts_matching_certificate() {
+        tokio::fs::create_dir_all("test_tmp")
+            .await
+            .expect("failed to create tmp dir");
+        let path = "test_tmp/download_to_file_pinned_match.txt";
+
+        let server =
+            TestServer::spawn_tls([response("200 OK", &[], b"data")], PINNED_CERT, PINNED_KEY);
+        let url = format!("{}/file", server.url);
+
+        let mut file = File::create(path).await.expect("failed to open");
+        download_to_file_pinned(reqwest::Client::builder(), &url, &mut file, PINNED_CERT)
+            .await
+            .expect("failed to download");
+        drop(file);
+
+        let data = tokio::fs::read(path).await.expect("failed to read");
+        assert!(data == b"data");
+
+        server.join();
+    }
+
+    #[cfg(feature = "download-to-file-pinned")]
+    #[tokio::test]
+    async fn pinned_rejects_mismatched_certificate() {
+        tokio::fs::create_dir_all("test_tmp")
+            .await
+            .expect("failed to create tmp dir");
+        let path = "test_tmp/download_to_file_pinned_mismatch.txt";
+
+        // The other certificate is trusted, so the handshake succeeds and only the pin fails.
+        let server =
+            TestServer::spawn_tls([response("200 OK", &[], b"data")], OTHER_CERT, OTHER_KEY);
+        let url = format!("{}/file", server.url);
+        let client_builder = reqwest::Client::builder().add_root_certificate(
+            reqwest::Certificate::from_der(OTHER_CERT).expect("invalid certificate"),
+        );
+
+        let mut file = File::create(path).await.expect("failed to open");
+        let error = download_to_file_pinned(client_builder, &url, &mut file, PINNED_CERT)
+            .await
+            .expect_err("accepted a mismatched certificate");
+        assert!(error.to_string().contains("does not match"));
+        assert!(file.metadata().await.expect("failed to get metadata").len() == 0);
+
+        server.join();
+    }
+
+    #[cfg(feature = "download-to-file-pinned")]
+    #[tokio::test]
+    async fn pinned_rejects_plain_http() {
+        tokio::fs::create_dir_all("test_tmp")
+            .await
+            .expect("failed to create tmp dir");
+        let path = "test_tmp/download_to_file_pinned_http.txt";
+
+        // Without TLS, there is no server certificate that could match.
+        let server = TestServer::spawn([response("200 OK", &[], b"data")]);
+        let url = format!("{}/file", server.url);
+
+        let mut file = File::create(path).await.expect("failed to open");
+        let error =
+            download_to_file_pinned(reqwest::Client::builder(), &url, &mut file, PINNED_CERT)
+                .await
+                .expect_err("accepted a response without TLS");
+        assert!(error.to_string().contains("not sent over TLS"));
+        assert!(file.metadata().await.expect("failed to get metadata").len() == 0);
+
+        server.join();
+    }
+
+    #[test]
+    fn parse_retry_after_works() {
+        let parse = |value: &'static str| {
+            let mut headers = reqwest::header::HeaderMap::new();
+            headers.insert(
+                RETRY_AFTER,
+                reqwest::header::HeaderValue::from_static(value),
+            );
+            parse_retry_after(&headers)
+        };
+
+        assert!(parse("120") == Some(Duration::from_secs(120)));
+        assert!(parse("Wed, 21 Oct 2015 07:28:00 GMT") == Some(Duration::ZERO));
+        assert!(parse("soon").is_none());
+    }
+
+    #[tokio::test]
+    async fn with_stats_counts_redirects() {
+        tokio::fs::create_dir_all("test_tmp")
+            .await
+            .expect("failed to create tmp dir");
+        let path = "test_tmp/download_to_file_with_stats.txt";
+
+        let redirect = || response("302 Found", &["Location: /redirect"], b"");
+        let server = TestServer::spawn([redirect(), redirect(), response("200 OK", &[], b"data")]);
+        let url = format!("{}/file", server.url);
+
+        let mut file = File::create(path).await.expect("failed to open");
+        let stats = download_to_file_with_stats(reqwest::Client::builder(), &url, &mut file)
+            .await
+            .expect("failed to download");
+        assert!(
+            stats
+                == DownloadStats {
+                    bytes: 4,
+                    redirects: 2
+                }
+        );
+
+        server.join();
+    }
+
+    #[cfg(feature = "jsonschema")]
+    #[tokio::test]
+    async fn json_validated_works() {
+        tokio::fs::create_dir_all("test_tmp")
+            .await
+            .expect("failed to create tmp dir");
+        let path = "test_tmp/download_json_validated.json";
+
+        let server = TestServer::spawn([
+            response("200 OK", &[], br#"{"name": "file"}"#),
+            response("200 OK", &[], br#"{"name": 1}"#),
+        ]);
+        let url = format!("{}/file", server.url);
+        let client = reqwest::Client::new();
+        let schema = serde_json::json!({
+            "type": "object",
+            "properties": {
+                "name": { "type": "string" }
+            }
+        });
+
+        let mut file = File::create(path).await.expect("failed to open");
+        download_json_validated(&client, &url, &mut file, &schema)
+            .await
+            .expect("failed to download");
+        drop(file);
+        let data = tokio::fs::read(path).await.expect("failed to read");
+        assert!(data == br#"{"name": "file"}"#);
+
+        let mut file = File::create(path).await.expect("failed to open");
+        let error = download_json_validated(&client, &url, &mut file, &schema)
+            .await
+            .expect_err("downloaded invalid json");
+        let error = error
+            .downcast_ref::<JsonValidationError>()
+            .expect("wrong error type");
+        assert!(error.errors().len() == 1);
+        drop(file);
+        let data = tokio::fs::read(path).await.expect("failed to read");
+        assert!(data.is_empty());
+
+        server.join();
+    }
+
+    #[tokio::test]
+    async fn resume_works() {
+        tokio::fs::create_dir_all("test_tmp")
+            .await
+            .expect("failed to create tmp dir");
+        let path = "test_tmp/download_resume.txt";
+
+        let server = TestServer::spawn([
+            response(
+                "206 Partial Content",
+                &["Content-Range: bytes 2-3/4"],
+                b"ta",
+            ),
+            response("200 OK", &[], b"data"),
+            response(
+                "416 Range Not Satisfiable",
+                &["Content-Range: bytes */4"],
+                b"",
+            ),
+        ]);
+        let url = format!("{}/file", server.url);
+        let client = reqwest::Client::new();
+
+        for existing in [&b"da"[..], b"garbage", b"data"] {
+            tokio::fs::write(path, existing)
+                .await
+                .expect("failed to write file");
+            let mut file = tokio::fs::OpenOptions::new()
+                .write(true)
+                .open(path)
+                .await
+                .expect("failed to open");
+            download_resume(&client, &url, &mut file)
+                .await
+                .expect("failed to download");
+            drop(file);
+
+            let data = tokio::fs::read(path).await.expect("failed to read");
+            assert!(data == b"data");
+        }
+
+        let requests = server.join();
+        assert!(requests[0].contains("range: bytes=2-"));
+        assert!(requests[1].contains("range: bytes=7-"));
+        assert!(requests[2].contains("range: bytes=4-"));
+    }
 }
@@ -1,11 +1,515 @@
-use crate::download_to_file;
+use crate::download_to_file::parse_content_range;
+use crate::download_to_file::response_mime_type;
+use crate::download_to_file::write_response_to_file;
+#[cfg(feature = "progress")]
+use crate::download_to_file::write_response_to_file_with_progress;
+#[cfg(feature = "download-to-path-resumable")]
+use crate::download_to_file::AfterChunkFn;
+use crate::download_to_file::DownloadProgress;
 use crate::with_push_extension;
 use crate::DropRemovePath;
+#[cfg(feature = "download-to-path-resumable")]
+use anyhow::bail;
+use anyhow::ensure;
 use anyhow::Context;
 use cfg_if::cfg_if;
+use reqwest::header::HeaderValue;
+use reqwest::header::ACCEPT_RANGES;
+use reqwest::header::CONTENT_DISPOSITION;
+use reqwest::header::CONTENT_LENGTH;
+use reqwest::header::CONTENT_TYPE;
+use reqwest::header::ETAG;
+use reqwest::header::IF_MODIFIED_SINCE;
+use reqwest::header::IF_NONE_MATCH;
+use reqwest::header::IF_RANGE;
+use reqwest::header::LAST_MODIFIED;
+use reqwest::header::RANGE;
+use reqwest::Method;
+use reqwest::StatusCode;
+#[cfg(feature = "download-to-path-resumable")]
+use std::io::SeekFrom;
+use std::ops::Range;
 use std::path::Path;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+use std::time::Instant;
+use std::time::SystemTime;
+use tokio::io::AsyncWriteExt;
+use tokio::sync::mpsc;
+use tokio::sync::Notify;
 use tracing::warn;
 
+/// Options for [`download_to_path_with_options`].
+#[derive(Debug, Clone)]
+pub struct DownloadToPathOptions {
+    /// The etag of a previously downloaded file.
+    etag: Option<String>,
+
+    /// The last modified time of a previously downloaded file.
+    last_modified: Option<SystemTime>,
+
+    /// Whether an existing file at the path should be overwritten.
+    overwrite: bool,
+
+    /// The extension added to the path to make the temporary path.
+    temp_suffix: String,
+
+    /// The timeout for getting the response headers.
+    header_timeout: Option<Duration>,
+
+    /// The timeout for getting each chunk of the body.
+    read_timeout: Option<Duration>,
+
+    /// The proxy to send requests through, instead of the proxies of the client.
+    proxy: Option<reqwest::Proxy>,
+
+    /// The MIME types that the response may have.
+    allowed_content_types: Option<Vec<String>>,
+
+    /// Whether the file name of the `Content-Disposition` header should be used.
+    use_content_disposition_filename: bool,
+
+    /// The method of the request.
+    method: Method,
+
+    /// The body of the request.
+    body: Option<bytes::Bytes>,
+
+    /// The SHA-256 fingerprint that the server certificate must have, as lowercase hex.
+    #[cfg(feature = "download-to-path-pinned")]
+    certificate_fingerprint: Option<String>,
+
+    /// The SHA-256 hash that an existing file must have to skip the download.
+    #[cfg(feature = "sha256")]
+    skip_if_hash_matches: Option<[u8; 32]>,
+
+    /// Whether a file with the SHA-256 hash of the file should be written next to it.
+    #[cfg(feature = "sha256")]
+    write_sha256_sidecar: bool,
+
+    /// The channel to send progress updates to while downloading.
+    progress_channel: Option<mpsc::Sender<DownloadProgress>>,
+
+    /// The notify to wake once the download is done.
+    completion_notify: Option<Arc<Notify>>,
+
+    /// Whether a metadata file should be written next to the file.
+    #[cfg(feature = "download-to-path-metadata")]
+    write_metadata: bool,
+
+    /// Whether an interrupted download should be resumed from a checkpoint.
+    #[cfg(feature = "download-to-path-resumable")]
+    resumable: bool,
+
+    /// The progress bar to advance while downloading.
+    #[cfg(feature = "progress")]
+    progress_bar: Option<indicatif::ProgressBar>,
+}
+
+impl DownloadToPathOptions {
+    /// Make a new [`DownloadToPathOptions`] with the default settings.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Only download the file if it does not match the given etag.
+    ///
+    /// The etag is sent in an `If-None-Match` header.
+    /// If the server responds with `304 Not Modified`,
+    /// the file at the path is left untouched and [`DownloadOutcome::Unchanged`] is returned.
+    pub fn etag(mut self, tag: &str) -> Self {
+        self.etag = Some(tag.into());
+        self
+    }
+
+    /// Only download the file if it was modified after the given time.
+    ///
+    /// The time is sent in an `If-Modified-Since` header.
+    /// If the server responds with `304 Not Modified`,
+    /// the file at the path is left untouched and [`DownloadOutcome::Unchanged`] is returned.
+    ///
+    /// This is usually the `last_modified` value of a previous [`DownloadOutcome::Downloaded`].
+    pub fn last_modified(mut self, last_modified: SystemTime) -> Self {
+        self.last_modified = Some(last_modified);
+        self
+    }
+
+    /// Set whether an existing file at the path should be overwritten.
+    ///
+    /// If false and a file already exists at the path,
+    /// no request is sent and [`DownloadOutcome::Skipped`] is returned.
+    /// This check is best-effort,
+    /// as a file that is created at the path during the download will still be overwritten.
+    ///
+    /// Defaults to true.
+    pub fn overwrite(mut self, overwrite: bool) -> Self {
+        self.overwrite = overwrite;
+        self
+    }
+
+    /// Only download the file if the response has one of the given MIME types.
+    ///
+    /// Types are given without parameters, like "text/plain", and are compared case-insensitively.
+    /// The `Content-Type` of the response is checked before anything is written,
+    /// so if it is missing or not one of the given types, an error is returned without creating the temporary file.
+    ///
+    /// Defaults to allowing any type.
+    pub fn allowed_content_types(mut self, types: &[&str]) -> Self {
+        self.allowed_content_types = Some(types.iter().map(|t| String::from(*t)).collect());
+        self
+    }
+
+    /// Set whether the file name that the server sends in a `Content-Disposition` header should be used.
+    ///
+    /// If the server sends a header like `attachment; filename="foo.zip"`,
+    /// the file is downloaded to a file with that name in the directory of the path, instead of the path.
+    /// A name that is not a plain file name, like one with a path separator or "..", is ignored,
+    /// so the server can't choose a different directory.
+    /// The path that was downloaded to is reported in [`DownloadOutcome::Downloaded`].
+    /// Checks made before the request, like whether the file should be overwritten, use the given path.
+    ///
+    /// See [`download_to_dir`] for downloading into a directory.
+    /// Since the segments of [`download_to_path_parallel`] need the path before they are requested,
+    /// it downloads without segments when this is enabled.
+    ///
+    /// Defaults to false.
+    pub fn use_content_disposition_filename(mut self, use_filename: bool) -> Self {
+        self.use_content_disposition_filename = use_filename;
+        self
+    }
+
+    /// Set the method of the request, for servers that need something other than a GET to start a download.
+    ///
+    /// [`download_to_path_parallel`] only uses ranged GET requests,
+    /// so it falls back to [`download_to_path_with_options`] for other methods.
+    ///
+    /// Defaults to GET.
+    pub fn method(mut self, method: Method) -> Self {
+        self.method = method;
+        self
+    }
+
+    /// Set the body of the request, usually along with a method like POST.
+    ///
+    /// Since options may be reused, like by [`download_to_path_with_fallback`],
+    /// the body must be in memory instead of being streamed.
+    ///
+    /// Defaults to no body.
+    pub fn body<B>(mut self, body: B) -> Self
+    where
+        B: Into<bytes::Bytes>,
+    {
+        self.body = Some(body.into());
+        self
+    }
+
+    /// Skip the download if the file at the path has the given SHA-256 hash.
+    ///
+    /// Before sending a request, the existing file is hashed.
+    /// If it matches, no request is sent and [`DownloadOutcome::Unchanged`] is returned.
+    /// If it is missing or does not match, the file is downloaded as normal.
+    #[cfg(feature = "sha256")]
+    pub fn skip_if_hash_matches(mut self, expected: [u8; 32]) -> Self {
+        self.skip_if_hash_matches = Some(expected);
+        self
+    }
+
+    /// Set whether a file with the SHA-256 hash of the file should be written next to it after a successful download.
+    ///
+    /// The hash is written to the path with a ".sha256" extension added, like "file.txt.sha256",
+    /// in the format of `sha256sum`, like "<hex digest>  file.txt".
+    /// Like the file itself, it is written to a temporary file first and renamed into place.
+    /// Nothing is written if the download is skipped or unchanged.
+    ///
+    /// Defaults to false.
+    #[cfg(feature = "sha256")]
+    pub fn write_sha256_sidecar(mut self, write: bool) -> Self {
+        self.write_sha256_sidecar = write;
+        self
+    }
+
+    /// Set the extension that is added to the path to make the temporary path.
+    ///
+    /// A leading "." is ignored, so "tmp" and ".tmp" are the same.
+    ///
+    /// Defaults to "part".
+    ///
+    /// # Panics
+    /// Panics if the suffix is empty, since the temporary path would then be the final path.
+    pub fn temp_suffix(mut self, suffix: &str) -> Self {
+        let suffix = suffix.strip_prefix('.').unwrap_or(suffix);
+        assert!(!suffix.is_empty(), "the temp suffix must not be empty");
+
+        self.temp_suffix = suffix.into();
+        self
+    }
+
+    /// Set the timeout for getting the response headers.
+    ///
+    /// This covers the whole time from sending the request until the response headers arrive,
+    /// which includes connecting, sending the request, and waiting for the server to respond.
+    /// A server that is slow to respond times out as well, even if connecting was fast.
+    /// It does not include downloading the body.
+    /// To only limit connecting, use [`reqwest::ClientBuilder::connect_timeout`] on the client instead.
+    ///
+    /// Defaults to no timeout.
+    pub fn header_timeout(mut self, timeout: Duration) -> Self {
+        self.header_timeout = Some(timeout);
+        self
+    }
+
+    /// Set the timeout for reading each chunk of the body.
+    ///
+    /// This is used to detect stalled downloads,
+    /// as a download that is making progress will never time out.
+    ///
+    /// Defaults to no timeout.
+    pub fn read_timeout(mut self, timeout: Duration) -> Self {
+        self.read_timeout = Some(timeout);
+        self
+    }
+
+    /// Send all requests through the given proxy.
+    ///
+    /// Since proxies belong to the client,
+    /// this is only used by [`download_to_path_with_client_builder`], which builds the client itself.
+    /// The download functions that take an existing client return an error if this is set.
+    ///
+    /// Defaults to using the client's proxies.
+    pub fn proxy(mut self, proxy: reqwest::Proxy) -> Self {
+        self.proxy = Some(proxy);
+        self
+    }
+
+    /// Send progress updates to the given channel as the file is downloaded.
+    ///
+    /// An update is sent after each chunk is written, waiting for space in the channel if it is full.
+    /// Unlike a callback, the receiver can be moved to another task.
+    /// The download continues as normal if the receiver is dropped.
+    ///
+    /// Since the segments of [`download_to_path_parallel`] are downloaded at the same time,
+    /// no updates are sent for them.
+    pub fn progress_channel(mut self, tx: mpsc::Sender<DownloadProgress>) -> Self {
+        self.progress_channel = Some(tx);
+        self
+    }
+
+    /// Wake a waiter of the given [`Notify`] once the download is done.
+    ///
+    /// This uses [`Notify::notify_one`] whether the download succeeded, failed, or was skipped,
+    /// so a task waiting on the notify does not need to poll for the result.
+    /// If no task is waiting yet, the next one to wait returns immediately.
+    pub fn completion_notify(mut self, notify: Arc<Notify>) -> Self {
+        self.completion_notify = Some(notify);
+        self
+    }
+
+    /// Only download the file if the server certificate has the given SHA-256 fingerprint.
+    ///
+    /// The fingerprint is the SHA-256 hash of the DER encoded leaf certificate, in hex.
+    /// Case and ":" separators are ignored, so "AB:CD:..." and "abcd..." are the same.
+    /// Every response is checked before anything is written,
+    /// and a response that does not match or was not sent over TLS is an error.
+    ///
+    /// Since the client must be built to report certificates,
+    /// this is only used by [`download_to_path_with_client_builder`], like [`DownloadToPathOptions::proxy`].
+    /// The certificate is still verified against the roots of the builder first,
+    /// so a self-signed certificate must also be added as a root to the builder.
+    #[cfg(feature = "download-to-path-pinned")]
+    pub fn pin_certificate_fingerprint(mut self, sha256_hex: &str) -> Self {
+        let fingerprint = sha256_hex
+            .chars()
+            .filter(|c| *c != ':')
+            .map(|c| c.to_ascii_lowercase())
+            .collect();
+        self.certificate_fingerprint = Some(fingerprint);
+        self
+    }
+
+    /// Whether interrupted downloads should be resumed.
+    fn is_resumable(&self) -> bool {
+        #[cfg(feature = "download-to-path-resumable")]
+        return self.resumable;
+
+        #[cfg(not(feature = "download-to-path-resumable"))]
+        false
+    }
+
+    /// Check that no options that belong to the client were set, as they can't be applied to an existing client.
+    fn ensure_no_client_options(&self) -> anyhow::Result<()> {
+        ensure!(
+            self.proxy.is_none(),
+            "a proxy can't be applied to an existing client, use download_to_path_with_client_builder"
+        );
+        #[cfg(feature = "download-to-path-pinned")]
+        ensure!(
+            self.certificate_fingerprint.is_none(),
+            "an existing client can't report certificates for pinning, use download_to_path_with_client_builder"
+        );
+
+        Ok(())
+    }
+
+    /// Apply the options that belong to the client to a client builder.
+    fn apply_to_client_builder(
+        &mut self,
+        mut client_builder: reqwest::ClientBuilder,
+    ) -> reqwest::ClientBuilder {
+        if let Some(proxy) = self.proxy.take() {
+            client_builder = client_builder.proxy(proxy);
+        }
+        #[cfg(feature = "download-to-path-pinned")]
+        if self.certificate_fingerprint.is_some() {
+            client_builder = client_builder.tls_info(true);
+        }
+
+        client_builder
+    }
+
+    /// Set whether a metadata file should be written next to the file after a successful download.
+    ///
+    /// The metadata is a [`DownloadMetadata`](crate::DownloadMetadata) serialized as JSON,
+    /// written to the path with a ".meta" extension added, like "file.txt.meta".
+    /// Like the file itself, it is written to a temporary file first and renamed into place.
+    /// Nothing is written if the download is skipped or unchanged.
+    ///
+    /// Defaults to false.
+    #[cfg(feature = "download-to-path-metadata")]
+    pub fn write_metadata(mut self, write: bool) -> Self {
+        self.write_metadata = write;
+        self
+    }
+
+    /// Set whether an interrupted download should be resumed on the next attempt.
+    ///
+    /// While downloading, a JSON checkpoint is written next to the temporary file,
+    /// with a ".checkpoint" extension added, like "file.txt.part.checkpoint".
+    /// It records the url, the number of bytes downloaded so far, and the size the server said the file has.
+    /// It is updated every 8 MiB, after the downloaded data is synced,
+    /// so that progress is kept even if the process is killed.
+    /// If the download fails, the temporary file and the checkpoint are kept instead of being removed.
+    /// The next download of the same url to the same path reads the checkpoint
+    /// and asks the server for the rest of the file with a `Range` header.
+    /// If the server sent an etag, an `If-Range` header is sent as well,
+    /// so that the whole file is downloaded again if it changed.
+    /// Once the download succeeds, the checkpoint is removed.
+    ///
+    /// Since the segments of [`download_to_path_parallel`] are not checkpointed,
+    /// it downloads without segments when this is enabled.
+    ///
+    /// Defaults to false.
+    #[cfg(feature = "download-to-path-resumable")]
+    pub fn resumable(mut self, resumable: bool) -> Self {
+        self.resumable = resumable;
+        self
+    }
+
+    /// Advance the given progress bar as the file is downloaded.
+    ///
+    /// The bar is incremented by the number of bytes written.
+    /// If the server reports the size of the file, it is used as the length of the bar.
+    /// The bar is not finished once the download completes, so the caller can choose how it is displayed.
+    #[cfg(feature = "progress")]
+    pub fn with_progress_bar(mut self, bar: indicatif::ProgressBar) -> Self {
+        self.progress_bar = Some(bar);
+        self
+    }
+}
+
+impl Default for DownloadToPathOptions {
+    fn default() -> Self {
+        Self {
+            etag: None,
+            last_modified: None,
+            overwrite: true,
+            temp_suffix: "part".into(),
+            header_timeout: None,
+            read_timeout: None,
+            proxy: None,
+            allowed_content_types: None,
+            use_content_disposition_filename: false,
+            method: Method::GET,
+            body: None,
+            #[cfg(feature = "download-to-path-pinned")]
+            certificate_fingerprint: None,
+            #[cfg(feature = "sha256")]
+            skip_if_hash_matches: None,
+            #[cfg(feature = "sha256")]
+            write_sha256_sidecar: false,
+            progress_channel: None,
+            completion_notify: None,
+            #[cfg(feature = "download-to-path-metadata")]
+            write_metadata: false,
+            #[cfg(feature = "download-to-path-resumable")]
+            resumable: false,
+            #[cfg(feature = "progress")]
+            progress_bar: None,
+        }
+    }
+}
+
+/// The outcome of a successful download.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DownloadOutcome {
+    /// The file was downloaded to the path.
+    Downloaded {
+        /// The path that the file was downloaded to.
+        ///
+        /// This is the given path,
+        /// unless [`DownloadToPathOptions::use_content_disposition_filename`] chose a different one.
+        path: PathBuf,
+
+        /// The size of the downloaded file, in bytes.
+        bytes: u64,
+
+        /// The time the server reported that the file was last modified.
+        ///
+        /// This is `None` if the server did not send a valid `Last-Modified` header.
+        last_modified: Option<SystemTime>,
+
+        /// The `ETag` header sent by the server.
+        ///
+        /// This can be passed to [`DownloadToPathOptions::etag`] for the next download.
+        etag: Option<String>,
+
+        /// The `Content-Type` header sent by the server.
+        content_type: Option<String>,
+
+        /// How long it took to download the body.
+        ///
+        /// This does not include sending the request or waiting for the response headers.
+        elapsed: Duration,
+    },
+
+    /// The server reported that the file is unchanged,
+    /// or the existing file matched the expected hash, if one was given.
+    ///
+    /// The file at the path was not touched.
+    Unchanged,
+
+    /// A file already existed at the path and overwriting was disabled.
+    ///
+    /// No request was sent.
+    Skipped,
+}
+
+impl DownloadOutcome {
+    /// Get the average speed of the download, in bytes per second.
+    ///
+    /// # Returns
+    /// Returns `None` if the file was not downloaded, or the download took no measurable time.
+    pub fn bytes_per_second(&self) -> Option<f64> {
+        match self {
+            Self::Downloaded { bytes, elapsed, .. } if !elapsed.is_zero() => {
+                // Precision loss only matters for files far larger than any real download.
+                Some(*bytes as f64 / elapsed.as_secs_f64())
+            }
+            _ => None,
+        }
+    }
+}
+
 /// Using the given client, download the file at a url to a given path.
 ///
 /// Note that this function will overwrite the file at the given path.
@@ -24,118 +528,2169 @@ pub async fn download_to_path<P>(client: &reqwest::Client, url: &str, path: P) -
 where
     P: AsRef<Path>,
 {
-    // Get the path.
-    let path = path.as_ref();
+    download_to_path_with_options(client, url, path, DownloadToPathOptions::new()).await?;
+    Ok(())
+}
 
-    // Create temporary path.
-    let temporary_path = with_push_extension(path, "part");
+/// Using the given client, download the file at a url to a given path, blocking the current thread.
+///
+/// This runs [`download_to_path`] on the runtime of the given [`tokio::runtime::Handle`],
+/// which is useful in non-async contexts like CLI tools and build scripts.
+///
+/// # Panics
+/// Like [`tokio::runtime::Handle::block_on`],
+/// this will panic if called from within an asynchronous execution context.
+///
+/// # Runtimes
+/// The runtime behind the handle must be able to drive IO while this function blocks.
+/// This is the case for multi-threaded runtimes.
+/// A current-thread runtime must be driven by another thread with [`tokio::runtime::Runtime::block_on`],
+/// otherwise the download will never make progress.
+pub fn download_to_path_blocking<P>(
+    handle: &tokio::runtime::Handle,
+    client: &reqwest::Client,
+    url: &str,
+    path: P,
+) -> anyhow::Result<()>
+where
+    P: AsRef<Path>,
+{
+    handle.block_on(download_to_path(client, url, path))
+}
 
-    // Setup to open the temporary file.
-    //
-    // We do NOT use mandatory locking on Windows.
-    // This is because the file would need to be dropped to be renamed,
-    // which leads to a race as we must release ALL locks to do so.
-    //
-    // TODO: On linux, consider probing for O_TMPFILE support somehow and create an unnamed tmp file and use linkat.
-    let mut open_options = tokio::fs::OpenOptions::new();
-    open_options.write(true);
+/// Using the given client and options, download the file at a url to a given path.
+///
+/// This behaves like [`download_to_path`],
+/// except that the request may be customized by the given [`DownloadToPathOptions`].
+/// The temporary file is only created once the server has responded with the file.
+///
+/// # Errors
+/// Returns an error if the options have a proxy or a pinned certificate,
+/// as they can't be applied to an existing client.
+/// Use [`download_to_path_with_client_builder`] instead.
+pub async fn download_to_path_with_options<P>(
+    client: &reqwest::Client,
+    url: &str,
+    path: P,
+    options: DownloadToPathOptions,
+) -> anyhow::Result<DownloadOutcome>
+where
+    P: AsRef<Path>,
+{
+    let completion_notify = options.completion_notify.clone();
+    let result = match options.ensure_no_client_options() {
+        Ok(()) => download_to_path_with_options_impl(client, url, path.as_ref(), options).await,
+        Err(error) => Err(error),
+    };
+    if let Some(completion_notify) = completion_notify {
+        completion_notify.notify_one();
+    }
 
-    // If we don't have a mechanism to prevent stomping,
-    // at least ensure that we can't stomp.
-    cfg_if! {
-        if #[cfg(any(windows, unix))] {
-            // We prevent stomping by locking somehow.
-            // Create and overwrite the temporary file.
-            open_options.create(true);
-        } else {
-            // If the temporary file exists, return an error.
-            open_options.create_new(true);
+    result
+}
+
+/// Using the given options, download the file at a url to a given path, building the client from a builder.
+///
+/// Since the proxy and certificate pinning belong to the client, a client is built once from the given builder,
+/// with the proxy of the options if one was set and with TLS info if a certificate was pinned,
+/// and is used for every request of the download.
+/// Otherwise, this is the same as [`download_to_path_with_options`].
+pub async fn download_to_path_with_client_builder<P>(
+    client_builder: reqwest::ClientBuilder,
+    url: &str,
+    path: P,
+    mut options: DownloadToPathOptions,
+) -> anyhow::Result<DownloadOutcome>
+where
+    P: AsRef<Path>,
+{
+    let completion_notify = options.completion_notify.clone();
+    let result = match options
+        .apply_to_client_builder(client_builder)
+        .build()
+        .context("failed to build client")
+    {
+        Ok(client) => {
+            download_to_path_with_options_impl(&client, url, path.as_ref(), options).await
         }
+        Err(error) => Err(error),
+    };
+    if let Some(completion_notify) = completion_notify {
+        completion_notify.notify_one();
     }
 
-    // Open the temporary file.
-    let temporary_file = open_options
-        .open(&temporary_path)
-        .await
-        .context("failed to create temporary file")?;
+    result
+}
 
-    // Create the remove handle for the temporary path.
-    let mut temporary_path = DropRemovePath::new(temporary_path);
+/// The implementation of [`download_to_path_with_options`], without the completion notification.
+async fn download_to_path_with_options_impl(
+    client: &reqwest::Client,
+    url: &str,
+    path: &Path,
+    options: DownloadToPathOptions,
+) -> anyhow::Result<DownloadOutcome> {
+    // Don't touch the file if it exists and we were asked not to overwrite it.
+    if !options.overwrite
+        && tokio::fs::try_exists(path)
+            .await
+            .context("failed to check if file exists")?
+    {
+        return Ok(DownloadOutcome::Skipped);
+    }
 
-    let result = async {
-        // Wrap the file in a lock, if the platform supports it.
-        cfg_if! {
-            if #[cfg(any(unix, windows))] {
-                let mut temporary_file_lock = fd_lock::RwLock::new(temporary_file);
-                let mut temporary_file = temporary_file_lock.try_write().context("failed to lock temporary file")?;
-            } else {
-                let mut temporary_file = temporary_file;
+    // Don't download the file if we already have it.
+    #[cfg(feature = "sha256")]
+    if let Some(expected) = options.skip_if_hash_matches {
+        if file_sha256(path).await? == Some(expected) {
+            return Ok(DownloadOutcome::Unchanged);
+        }
+    }
+
+    // Send the request.
+    let mut request = client.request(options.method.clone(), url);
+    if let Some(body) = options.body.clone() {
+        request = request.body(body);
+    }
+    let request = add_conditional_headers(request, &options)?;
+
+    // Continue from where an interrupted download stopped, if we can.
+    #[cfg(feature = "download-to-path-resumable")]
+    let checkpoint = match options.resumable {
+        true => read_checkpoint(path, &options.temp_suffix, url).await,
+        false => None,
+    };
+    #[cfg(feature = "download-to-path-resumable")]
+    let request = match checkpoint.as_ref() {
+        Some(checkpoint) => {
+            let request = request.header(RANGE, format!("bytes={}-", checkpoint.downloaded));
+            match checkpoint.etag.as_deref() {
+                Some(etag) => request.header(IF_RANGE, etag),
+                None => request,
             }
         }
+        None => request,
+    };
 
-        // Perform download.
-        download_to_file(client, url, &mut temporary_file)
-            .await?;
+    let response = send_request(request, &options).await?;
 
-        // Perform rename from temporary file path to actual file path.
-        tokio::fs::rename(&temporary_path, &path)
-            .await
-            .context("failed to rename temporary file")?;
+    // The server told us that our copy is up to date.
+    if response.status() == StatusCode::NOT_MODIFIED {
+        return Ok(DownloadOutcome::Unchanged);
+    }
 
-        // Ensure that the file handle is dropped AFTER we rename.
-        //
-        // Uwrap the file from the file lock.
-        cfg_if! {
-            if #[cfg(any(unix, windows))] {
-                // Unlock lock.
-                drop(temporary_file);
+    // The checkpoint is wrong, so don't use it again.
+    #[cfg(feature = "download-to-path-resumable")]
+    if checkpoint.is_some() && response.status() == StatusCode::RANGE_NOT_SATISFIABLE {
+        remove_checkpoint(path, &options.temp_suffix).await;
+        bail!("the server rejected the range of the checkpoint");
+    }
 
-                // Get file from lock.
-                let temporary_file = temporary_file_lock.into_inner();
-            }
+    let response = response.error_for_status()?;
+    check_content_type(&response, &options)?;
+
+    // Let the server choose the file name, if we were asked to.
+    let content_disposition_path = options
+        .use_content_disposition_filename
+        .then(|| content_disposition_filename(response.headers()))
+        .flatten()
+        .map(|file_name| path.with_file_name(file_name));
+    if let Some(content_disposition_path) = content_disposition_path.as_deref() {
+        if !options.overwrite
+            && tokio::fs::try_exists(content_disposition_path)
+                .await
+                .context("failed to check if file exists")?
+        {
+            return Ok(DownloadOutcome::Skipped);
         }
+    }
+    #[cfg(feature = "download-to-path-resumable")]
+    let request_path = path;
+    let path = content_disposition_path.as_deref().unwrap_or(path);
 
-        drop(temporary_file.into_std());
+    let last_modified = parse_last_modified(response.headers());
+    let etag = header_string(response.headers(), ETAG);
+    let content_type = header_string(response.headers(), CONTENT_TYPE);
+    #[cfg(feature = "download-to-path-metadata")]
+    let headers = options.write_metadata.then(|| response.headers().clone());
 
-        Ok(())
+    #[cfg(feature = "progress")]
+    if let (Some(progress_bar), Some(content_length)) =
+        (options.progress_bar.as_ref(), response.content_length())
+    {
+        progress_bar.set_length(content_length);
     }
-    .await;
 
-    match result.as_ref() {
-        Ok(()) => {
-            // Persist the file,
-            // since it was renamed and we don't want to remove a non-existent file.
-            temporary_path.persist();
-        }
-        Err(_error) => {
-            // Try to clean up the temporary file before returning.
-            if let Err((mut temporary_path, error)) = temporary_path.try_drop().await {
-                // Don't try to delete the file again.
-                temporary_path.persist();
+    // Perform download.
+    let start = Instant::now();
+    #[cfg(feature = "download-to-path-resumable")]
+    let bytes = if options.resumable {
+        write_resumable(request_path, path, url, response, &options, checkpoint).await?
+    } else {
+        write_via_temporary_file(path, &options.temp_suffix, async |file| {
+            write_response(response, file, &options).await
+        })
+        .await?
+    };
+    #[cfg(not(feature = "download-to-path-resumable"))]
+    let bytes = write_via_temporary_file(path, &options.temp_suffix, async |file| {
+        write_response(response, file, &options).await
+    })
+    .await?;
+    let elapsed = start.elapsed();
 
-                // Returning the original error is more important,
-                // so we just log the temporary file error here.
-                warn!("failed to delete temporary file '{error}'");
+    #[cfg(feature = "download-to-path-metadata")]
+    if let Some(headers) = headers {
+        let metadata = crate::DownloadMetadata::from_headers(url, &headers, bytes);
+        write_metadata(path, &options.temp_suffix, &metadata).await?;
+    }
+
+    #[cfg(feature = "sha256")]
+    if options.write_sha256_sidecar {
+        write_sha256_sidecar(path, &options.temp_suffix).await?;
+    }
+
+    Ok(DownloadOutcome::Downloaded {
+        path: path.into(),
+        bytes,
+        last_modified,
+        etag,
+        content_type,
+        elapsed,
+    })
+}
+
+/// Using the given client and options, download the file at a url into a directory.
+///
+/// The file is named by the percent-decoded last segment of the url's path, or "download" if it has none,
+/// and is downloaded with [`download_to_path_with_options`].
+/// If the decoded name is not a plain file name, like one with a path separator or "..", an error is returned instead,
+/// so that the file is never written outside of the directory.
+/// Use [`DownloadToPathOptions::use_content_disposition_filename`] to let the server choose the name instead.
+/// The path that was downloaded to is reported in [`DownloadOutcome::Downloaded`].
+pub async fn download_to_dir<P>(
+    client: &reqwest::Client,
+    url: &str,
+    dir: P,
+    options: DownloadToPathOptions,
+) -> anyhow::Result<DownloadOutcome>
+where
+    P: AsRef<Path>,
+{
+    let parsed_url = reqwest::Url::parse(url).context("failed to parse url")?;
+    let file_name = url_file_name(&parsed_url)?;
+    let path = dir.as_ref().join(file_name);
+
+    download_to_path_with_options(client, url, path, options).await
+}
+
+/// Get the file name for a url, from the percent-decoded last segment of its path.
+fn url_file_name(url: &reqwest::Url) -> anyhow::Result<String> {
+    let Some(segment) = url
+        .path_segments()
+        .and_then(|mut segments| segments.next_back())
+        .filter(|segment| !segment.is_empty())
+    else {
+        return Ok("download".into());
+    };
+
+    let file_name = percent_decode(segment).with_context(|| {
+        format!("the url file name \"{segment}\" is not valid percent-encoded UTF-8")
+    })?;
+    ensure!(
+        is_plain_file_name(&file_name),
+        "the url file name \"{file_name}\" is not a plain file name"
+    );
+
+    Ok(file_name)
+}
+
+/// Check whether a decoded file name from a server names a file in the download directory.
+///
+/// A plain file name is not empty, is not "." or "..", and has no path separators.
+/// Both separators are checked, as a name from a Windows server may use either.
+fn is_plain_file_name(file_name: &str) -> bool {
+    !file_name.is_empty()
+        && file_name != "."
+        && file_name != ".."
+        && !file_name.contains(['/', '\\'])
+}
+
+/// Using the given client and options, download a file to a given path from the first url that works.
+///
+/// This is useful for mirrors.
+/// Each url is tried in order with [`download_to_path_with_options`], logging each failure,
+/// until one succeeds.
+///
+/// # Errors
+/// Returns the error of the last url if all urls fail, or an error if no urls were given.
+pub async fn download_to_path_with_fallback<P>(
+    client: &reqwest::Client,
+    urls: &[&str],
+    path: P,
+    options: DownloadToPathOptions,
+) -> anyhow::Result<DownloadOutcome>
+where
+    P: AsRef<Path>,
+{
+    let path = path.as_ref();
+
+    // Only notify once all urls were tried.
+    let completion_notify = options.completion_notify.clone();
+    let result = async {
+        let mut last_error = None;
+        for url in urls {
+            match download_to_path_with_options_impl(client, url, path, options.clone()).await {
+                Ok(outcome) => return Ok(outcome),
+                Err(error) => {
+                    warn!("failed to download \"{url}\": {error:?}");
+                    last_error = Some(error.context(format!("failed to download \"{url}\"")));
+                }
             }
         }
+
+        let error = last_error.unwrap_or_else(|| anyhow::anyhow!("no urls were given"));
+        Err(error.context("all urls failed"))
+    }
+    .await;
+    if let Some(completion_notify) = completion_notify {
+        completion_notify.notify_one();
     }
 
     result
 }
 
-#[cfg(test)]
-mod test {
-    use super::*;
+/// Using the given client and options, download the file at a url to a given path in parallel segments.
+///
+/// A HEAD request is sent first to get the size of the file and to check that the server supports range requests.
+/// The file is then split into `segments` ranges, which are downloaded concurrently.
+/// Once all segments are downloaded, they are joined in order and the result is renamed to the path,
+/// just like [`download_to_path`].
+/// If the server sent an etag, each segment is requested with an `If-Range` header,
+/// so that the download fails instead of mixing segments if the file changes while downloading.
+///
+/// If the server does not support range requests or does not report the file size,
+/// this falls back to [`download_to_path_with_options`].
+///
+/// # Temporary Files
+/// Each segment is downloaded to the temporary path with its index as an extra extension, like "file.txt.part.0".
+/// These files are removed once the segments are joined, or when the download fails.
+///
+/// # Errors
+/// Like [`download_to_path_with_options`], this returns an error if the options have a proxy or a pinned certificate.
+pub async fn download_to_path_parallel<P>(
+    client: &reqwest::Client,
+    url: &str,
+    path: P,
+    segments: u32,
+    options: DownloadToPathOptions,
+) -> anyhow::Result<DownloadOutcome>
+where
+    P: AsRef<Path>,
+{
+    let completion_notify = options.completion_notify.clone();
+    let result = match options.ensure_no_client_options() {
+        Ok(()) => {
+            download_to_path_parallel_impl(client, url, path.as_ref(), segments, options).await
+        }
+        Err(error) => Err(error),
+    };
+    if let Some(completion_notify) = completion_notify {
+        completion_notify.notify_one();
+    }
 
-    #[tokio::test]
-    async fn it_works() {
-        tokio::fs::create_dir_all("test_tmp")
-            .await
-            .expect("failed to create tmp dir");
+    result
+}
 
-        let client = reqwest::Client::new();
-        download_to_path(&client, "http://google.com", "test_tmp/google.html")
+/// The implementation of [`download_to_path_parallel`], without the completion notification.
+async fn download_to_path_parallel_impl(
+    client: &reqwest::Client,
+    url: &str,
+    path: &Path,
+    segments: u32,
+    options: DownloadToPathOptions,
+) -> anyhow::Result<DownloadOutcome> {
+    // Segments are fetched with ranged GET requests to a known path.
+    if options.method != Method::GET
+        || options.use_content_disposition_filename
+        || options.is_resumable()
+    {
+        return download_to_path_with_options_impl(client, url, path, options).await;
+    }
+
+    // Don't touch the file if it exists and we were asked not to overwrite it.
+    if !options.overwrite
+        && tokio::fs::try_exists(path)
             .await
-            .expect("failed to download");
+            .context("failed to check if file exists")?
+    {
+        return Ok(DownloadOutcome::Skipped);
+    }
+
+    // Don't download the file if we already have it.
+    #[cfg(feature = "sha256")]
+    if let Some(expected) = options.skip_if_hash_matches {
+        if file_sha256(path).await? == Some(expected) {
+            return Ok(DownloadOutcome::Unchanged);
+        }
+    }
+
+    // Probe the server.
+    let request = add_conditional_headers(client.head(url), &options)?;
+    let response = send_request(request, &options).await?;
+    if response.status() == StatusCode::NOT_MODIFIED {
+        return Ok(DownloadOutcome::Unchanged);
+    }
+    let response = response.error_for_status()?;
+    check_content_type(&response, &options)?;
+
+    let headers = response.headers();
+    let supports_ranges = headers
+        .get(ACCEPT_RANGES)
+        .is_some_and(|accept_ranges| accept_ranges == "bytes");
+    let content_length: Option<u64> = headers
+        .get(CONTENT_LENGTH)
+        .and_then(|content_length| content_length.to_str().ok())
+        .and_then(|content_length| content_length.parse().ok());
+    let etag = header_string(headers, ETAG);
+    let last_modified = parse_last_modified(headers);
+    let content_type = header_string(headers, CONTENT_TYPE);
+    #[cfg(feature = "download-to-path-metadata")]
+    let metadata_headers = options.write_metadata.then(|| headers.clone());
+
+    // Each segment needs at least 1 byte.
+    let segments =
+        content_length.map_or(1, |content_length| u64::from(segments).min(content_length));
+    let content_length = match content_length {
+        Some(content_length) if supports_ranges && segments > 1 => content_length,
+        _ => {
+            return download_to_path_with_options_impl(client, url, path, options).await;
+        }
+    };
+
+    #[cfg(feature = "progress")]
+    if let Some(progress_bar) = options.progress_bar.as_ref() {
+        progress_bar.set_length(content_length);
+    }
+
+    // Download the segments.
+    let start = Instant::now();
+    let temporary_path = with_push_extension(path, &options.temp_suffix);
+    let segment_size = content_length / segments;
+    let mut tasks = tokio::task::JoinSet::new();
+    for index in 0..segments {
+        // The last segment gets the remainder.
+        let start = index * segment_size;
+        let end = if index + 1 == segments {
+            content_length
+        } else {
+            start + segment_size
+        };
+
+        let mut request = client
+            .get(url)
+            .header(RANGE, format!("bytes={start}-{}", end - 1));
+        if let Some(etag) = etag.as_deref() {
+            request = request.header(IF_RANGE, etag);
+        }
+        let segment_path = with_push_extension(&temporary_path, index.to_string());
+        let options = DownloadToPathOptions {
+            progress_channel: None,
+            ..options.clone()
+        };
+        tasks.spawn(async move {
+            let result = download_segment(request, segment_path, start..end, &options)
+                .await
+                .with_context(|| format!("failed to download segment {index}"));
+
+            (index, result)
+        });
+    }
+
+    // Wait for all segments.
+    //
+    // If any segment fails, dropping the tasks will cancel the rest,
+    // and dropping the segment paths will remove the finished ones.
+    let mut segment_paths = Vec::with_capacity(tasks.len());
+    while let Some(result) = tasks.join_next().await {
+        let (index, result) = result.context("segment task failed")?;
+        segment_paths.push((index, result?));
+    }
+    segment_paths.sort_by_key(|(index, _)| *index);
+    let elapsed = start.elapsed();
+
+    // Join the segments.
+    let bytes = write_via_temporary_file(path, &options.temp_suffix, async |file| {
+        for (_, segment_path) in segment_paths.iter() {
+            let mut segment_file = tokio::fs::File::open(&**segment_path)
+                .await
+                .context("failed to open segment file")?;
+            tokio::io::copy(&mut segment_file, file)
+                .await
+                .context("failed to copy segment")?;
+        }
+
+        file.flush().await.context("failed to flush file")?;
+        file.sync_all().await.context("failed to sync file data")?;
+
+        Ok(content_length)
+    })
+    .await?;
+
+    // Clean up the segments.
+    for (_, segment_path) in segment_paths {
+        if let Err((mut segment_path, error)) = segment_path.try_drop().await {
+            segment_path.persist();
+            warn!("failed to delete segment file '{error}'");
+        }
+    }
+
+    #[cfg(feature = "download-to-path-metadata")]
+    if let Some(headers) = metadata_headers {
+        let metadata = crate::DownloadMetadata::from_headers(url, &headers, bytes);
+        write_metadata(path, &options.temp_suffix, &metadata).await?;
+    }
+
+    #[cfg(feature = "sha256")]
+    if options.write_sha256_sidecar {
+        write_sha256_sidecar(path, &options.temp_suffix).await?;
+    }
+
+    Ok(DownloadOutcome::Downloaded {
+        path: path.into(),
+        bytes,
+        last_modified,
+        etag,
+        content_type,
+        elapsed,
+    })
+}
+
+/// Download one segment of a file with a range request.
+///
+/// # Returns
+/// Returns a handle that removes the segment file once it is no longer needed.
+async fn download_segment(
+    request: reqwest::RequestBuilder,
+    segment_path: PathBuf,
+    range: Range<u64>,
+    options: &DownloadToPathOptions,
+) -> anyhow::Result<DropRemovePath> {
+    let response = send_request(request, options).await?.error_for_status()?;
+
+    // If the file changed, the server sends the whole file instead.
+    ensure!(
+        response.status() == StatusCode::PARTIAL_CONTENT,
+        "server did not send a partial response"
+    );
+    let (start, _) = parse_content_range(&response)?;
+    ensure!(
+        start == Some(range.start),
+        "server sent a range that does not start at {}",
+        range.start
+    );
+
+    let mut segment_file = tokio::fs::File::create(&segment_path)
+        .await
+        .context("failed to create segment file")?;
+    let segment_path = DropRemovePath::new(segment_path);
+
+    let bytes = write_response(response, &mut segment_file, options).await?;
+    ensure!(
+        bytes == range.end - range.start,
+        "server sent a segment of the wrong size"
+    );
+
+    Ok(segment_path)
+}
+
+/// Add the `If-None-Match` and `If-Modified-Since` headers to a request, if the options ask for them.
+fn add_conditional_headers(
+    mut request: reqwest::RequestBuilder,
+    options: &DownloadToPathOptions,
+) -> anyhow::Result<reqwest::RequestBuilder> {
+    if let Some(etag) = options.etag.as_deref() {
+        let etag = HeaderValue::from_str(etag).context("invalid etag")?;
+        request = request.header(IF_NONE_MATCH, etag);
+    }
+    if let Some(last_modified) = options.last_modified {
+        request = request.header(IF_MODIFIED_SINCE, httpdate::fmt_http_date(last_modified));
+    }
+
+    Ok(request)
+}
+
+/// Hash the file at the given path with SHA-256.
+///
+/// # Returns
+/// Returns `None` if there is no file at the path.
+#[cfg(feature = "sha256")]
+async fn file_sha256(path: &Path) -> anyhow::Result<Option<[u8; 32]>> {
+    use sha2::Digest;
+    use tokio::io::AsyncReadExt;
+
+    let mut file = match tokio::fs::File::open(path).await {
+        Ok(file) => file,
+        Err(error) if error.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(error) => return Err(error).context("failed to open existing file"),
+    };
+
+    let mut hasher = sha2::Sha256::new();
+    let mut buffer = vec![0; 64 * 1024];
+    loop {
+        let n = file
+            .read(&mut buffer)
+            .await
+            .context("failed to read existing file")?;
+        if n == 0 {
+            break;
+        }
+        hasher.update(&buffer[..n]);
+    }
+
+    Ok(Some(hasher.finalize().into()))
+}
+
+/// Check that the response has one of the allowed content types of the options, if there are any.
+fn check_content_type(
+    response: &reqwest::Response,
+    options: &DownloadToPathOptions,
+) -> anyhow::Result<()> {
+    let Some(allowed_content_types) = options.allowed_content_types.as_deref() else {
+        return Ok(());
+    };
+
+    let mime_type = response_mime_type(response).context("missing content-type header")?;
+    ensure!(
+        allowed_content_types
+            .iter()
+            .any(|allowed| allowed.eq_ignore_ascii_case(mime_type)),
+        "content-type {mime_type} is not one of the allowed types {allowed_content_types:?}"
+    );
+
+    Ok(())
+}
+
+/// Get the file name from a `Content-Disposition` header, if there is a usable one.
+///
+/// An RFC 5987 `filename*` parameter in UTF-8 is preferred over a `filename` parameter.
+/// A name that is not a plain file name is not usable, so it can't escape the download directory.
+fn content_disposition_filename(headers: &reqwest::header::HeaderMap) -> Option<String> {
+    let content_disposition = headers.get(CONTENT_DISPOSITION)?.to_str().ok()?;
+
+    let mut filename = None;
+    let mut extended_filename = None;
+    for (name, value) in content_disposition_params(content_disposition) {
+        if name.eq_ignore_ascii_case("filename") {
+            filename = Some(value);
+        } else if name.eq_ignore_ascii_case("filename*") {
+            extended_filename = value
+                .split_once("''")
+                .filter(|(charset, _)| charset.eq_ignore_ascii_case("utf-8"))
+                .and_then(|(_, value)| percent_decode(value));
+        }
+    }
+
+    let filename = extended_filename.or(filename)?;
+
+    let filename = filename.trim();
+    if !is_plain_file_name(filename) {
+        return None;
+    }
+
+    Some(filename.into())
+}
+
+/// Split the parameters of a `Content-Disposition` header into names and unquoted values.
+fn content_disposition_params(value: &str) -> Vec<(&str, String)> {
+    let mut params = Vec::new();
+    let mut rest = value.split_once(';').map_or("", |(_, rest)| rest);
+    while let Some((name, after_name)) = rest.split_once('=') {
+        let name = name.trim_start_matches([';', ' ', '\t']).trim();
+        let after_name = after_name.trim_start();
+
+        let value = if let Some(quoted) = after_name.strip_prefix('"') {
+            let mut value = String::new();
+            let mut chars = quoted.char_indices();
+            let mut end = quoted.len();
+            while let Some((i, c)) = chars.next() {
+                match c {
+                    '\\' => value.extend(chars.next().map(|(_, c)| c)),
+                    '"' => {
+                        end = i + 1;
+                        break;
+                    }
+                    c => value.push(c),
+                }
+            }
+            rest = quoted[end..].split_once(';').map_or("", |(_, rest)| rest);
+            value
+        } else {
+            let (value, after_value) = after_name.split_once(';').unwrap_or((after_name, ""));
+            rest = after_value;
+            value.trim().into()
+        };
+
+        params.push((name, value));
+    }
+
+    params
+}
+
+/// Decode a percent-encoded UTF-8 string.
+fn percent_decode(value: &str) -> Option<String> {
+    let mut bytes = Vec::with_capacity(value.len());
+    let mut iter = value.bytes();
+    while let Some(byte) = iter.next() {
+        if byte == b'%' {
+            let hex = [iter.next()?, iter.next()?];
+            let hex = std::str::from_utf8(&hex).ok()?;
+            bytes.push(u8::from_str_radix(hex, 16).ok()?);
+        } else {
+            bytes.push(byte);
+        }
+    }
+
+    String::from_utf8(bytes).ok()
+}
+
+/// Get a header of a response as a string.
+///
+/// # Returns
+/// Returns `None` if the header is missing or is not valid UTF-8.
+fn header_string(
+    headers: &reqwest::header::HeaderMap,
+    name: reqwest::header::HeaderName,
+) -> Option<String> {
+    let value = headers.get(name)?.to_str().ok()?;
+    Some(value.into())
+}
+
+/// Parse the `Last-Modified` header of a response.
+///
+/// # Returns
+/// Returns `None` if the header is missing or invalid.
+fn parse_last_modified(headers: &reqwest::header::HeaderMap) -> Option<SystemTime> {
+    let last_modified = headers.get(LAST_MODIFIED)?.to_str().ok()?;
+    httpdate::parse_http_date(last_modified).ok()
+}
+
+/// Write the metadata of a download next to the file at the given path.
+#[cfg(feature = "download-to-path-metadata")]
+async fn write_metadata(
+    path: &Path,
+    temp_suffix: &str,
+    metadata: &crate::DownloadMetadata,
+) -> anyhow::Result<()> {
+    let data = serde_json::to_vec_pretty(metadata).context("failed to serialize metadata")?;
+
+    write_sidecar(path, "meta", temp_suffix, &data)
+        .await
+        .context("failed to write metadata file")
+}
+
+/// Write the SHA-256 hash of the file at the given path next to it, in the format of `sha256sum`.
+#[cfg(feature = "sha256")]
+async fn write_sha256_sidecar(path: &Path, temp_suffix: &str) -> anyhow::Result<()> {
+    let hash = file_sha256(path)
+        .await?
+        .context("the downloaded file is missing")?;
+    let hash: String = hash.iter().map(|byte| format!("{byte:02x}")).collect();
+    let file_name = path
+        .file_name()
+        .context("the path has no file name")?
+        .to_string_lossy();
+    let data = format!("{hash}  {file_name}\n");
+
+    write_sidecar(path, "sha256", temp_suffix, data.as_bytes())
+        .await
+        .context("failed to write sha256 file")
+}
+
+/// Write data to a file next to the file at the given path, with the given extension added.
+#[cfg(any(
+    feature = "download-to-path-metadata",
+    feature = "sha256",
+    feature = "download-to-path-resumable"
+))]
+async fn write_sidecar(
+    path: &Path,
+    extension: &str,
+    temp_suffix: &str,
+    data: &[u8],
+) -> anyhow::Result<()> {
+    let sidecar_path = with_push_extension(path, extension);
+
+    write_via_temporary_file(&sidecar_path, temp_suffix, async |file| {
+        file.write_all(data).await.context("failed to write data")?;
+        file.flush().await.context("failed to flush file")?;
+        file.sync_all().await.context("failed to sync file data")?;
+
+        Ok(u64::try_from(data.len())?)
+    })
+    .await?;
+
+    Ok(())
+}
+
+/// The progress of an interrupted download, written next to its temporary file.
+#[cfg(feature = "download-to-path-resumable")]
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+struct Checkpoint {
+    /// The url being downloaded.
+    url: String,
+
+    /// The number of bytes of the temporary file that were downloaded.
+    downloaded: u64,
+
+    /// The size of the whole file, if the server said.
+    total: Option<u64>,
+
+    /// The etag of the file, if the server sent one.
+    etag: Option<String>,
+}
+
+/// Get the path of the checkpoint for a download to the given path.
+#[cfg(feature = "download-to-path-resumable")]
+fn checkpoint_path(path: &Path, temp_suffix: &str) -> PathBuf {
+    with_push_extension(with_push_extension(path, temp_suffix), "checkpoint")
+}
+
+/// Read the checkpoint for a download of a url to the given path, if there is a usable one.
+///
+/// A checkpoint that can't be read, is for a different url,
+/// or claims more bytes than the temporary file has, is ignored.
+#[cfg(feature = "download-to-path-resumable")]
+async fn read_checkpoint(path: &Path, temp_suffix: &str, url: &str) -> Option<Checkpoint> {
+    let data = tokio::fs::read(checkpoint_path(path, temp_suffix))
+        .await
+        .ok()?;
+    let checkpoint: Checkpoint = serde_json::from_slice(&data).ok()?;
+    if checkpoint.url != url || checkpoint.downloaded == 0 {
+        return None;
+    }
+
+    let temporary_path = with_push_extension(path, temp_suffix);
+    let temporary_len = tokio::fs::metadata(temporary_path).await.ok()?.len();
+    if temporary_len < checkpoint.downloaded {
+        return None;
+    }
+
+    Some(checkpoint)
+}
+
+/// Remove the checkpoint for a download to the given path, logging any failure.
+#[cfg(feature = "download-to-path-resumable")]
+async fn remove_checkpoint(path: &Path, temp_suffix: &str) {
+    match tokio::fs::remove_file(checkpoint_path(path, temp_suffix)).await {
+        Ok(()) => {}
+        Err(error) if error.kind() == std::io::ErrorKind::NotFound => {}
+        Err(error) => warn!("failed to delete checkpoint file '{error}'"),
+    }
+}
+
+/// Write the body of a response to the temporary file of `request_path`, checkpointing the progress,
+/// and rename it to `path` on success.
+///
+/// The response should be for a request made with the range of the given checkpoint, if there is one.
+/// Unlike [`write_via_temporary_file`], the temporary file is kept if the download fails,
+/// and the checkpoint is updated with how many bytes were written.
+///
+/// # Returns
+/// Returns the size of the whole file.
+#[cfg(feature = "download-to-path-resumable")]
+async fn write_resumable(
+    request_path: &Path,
+    path: &Path,
+    url: &str,
+    response: reqwest::Response,
+    options: &DownloadToPathOptions,
+    checkpoint: Option<Checkpoint>,
+) -> anyhow::Result<u64> {
+    use tokio::io::AsyncSeekExt;
+
+    let temporary_path = with_push_extension(request_path, &options.temp_suffix);
+
+    // Find where the body starts in the file.
+    let (start, total) = match checkpoint {
+        Some(checkpoint) if response.status() == StatusCode::PARTIAL_CONTENT => {
+            let (range_start, total) = parse_content_range(&response)?;
+
+            // The checkpoint doesn't match what the server has, so don't use it again.
+            if range_start != Some(checkpoint.downloaded) {
+                remove_checkpoint(request_path, &options.temp_suffix).await;
+                bail!(
+                    "server sent a range that does not start at {}",
+                    checkpoint.downloaded
+                );
+            }
+
+            (checkpoint.downloaded, total.or(checkpoint.total))
+        }
+        // The server ignored our range or we had none, so start over.
+        _ => (0, response.content_length()),
+    };
+    let etag = header_string(response.headers(), ETAG);
+
+    let temporary_file = tokio::fs::OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .open(&temporary_path)
+        .await
+        .context("failed to open temporary file")?;
+
+    cfg_if! {
+        if #[cfg(any(unix, windows))] {
+            let mut temporary_file_lock = crate::AsyncFileLock::new(temporary_file);
+            let mut temporary_file = temporary_file_lock.try_lock().context("failed to lock temporary file")?;
+        } else {
+            let mut temporary_file = temporary_file;
+        }
+    }
+
+    temporary_file
+        .set_len(start)
+        .await
+        .context("failed to truncate temporary file")?;
+    temporary_file
+        .seek(SeekFrom::Start(start))
+        .await
+        .context("failed to seek temporary file")?;
+
+    // Record where we are before writing, in case we are interrupted.
+    let mut checkpoint = Checkpoint {
+        url: url.into(),
+        downloaded: start,
+        total,
+        etag,
+    };
+    write_checkpoint(&temporary_path, &options.temp_suffix, &checkpoint).await?;
+
+    let result = write_response_with_checkpoints(
+        response,
+        &mut temporary_file,
+        &temporary_path,
+        options,
+        &mut checkpoint,
+    )
+    .await;
+    let bytes = match result {
+        Ok(bytes) => start + bytes,
+        Err(error) => {
+            // Keep what was written for the next attempt.
+            let result = async {
+                let position = temporary_file
+                    .stream_position()
+                    .await
+                    .context("failed to get file position")?;
+                temporary_file
+                    .set_len(position)
+                    .await
+                    .context("failed to truncate temporary file")?;
+                temporary_file
+                    .sync_all()
+                    .await
+                    .context("failed to sync file data")?;
+
+                checkpoint.downloaded = position;
+                write_checkpoint(&temporary_path, &options.temp_suffix, &checkpoint).await
+            }
+            .await;
+            if let Err(checkpoint_error) = result {
+                warn!("failed to update checkpoint '{checkpoint_error:?}'");
+            }
+
+            return Err(error);
+        }
+    };
+
+    tokio::fs::rename(&temporary_path, path)
+        .await
+        .context("failed to rename temporary file")?;
+    remove_checkpoint(request_path, &options.temp_suffix).await;
+
+    Ok(bytes)
+}
+
+/// Write the checkpoint for the given temporary file.
+#[cfg(feature = "download-to-path-resumable")]
+async fn write_checkpoint(
+    temporary_path: &Path,
+    temp_suffix: &str,
+    checkpoint: &Checkpoint,
+) -> anyhow::Result<()> {
+    let data = serde_json::to_vec(checkpoint).context("failed to serialize checkpoint")?;
+    write_sidecar(temporary_path, "checkpoint", temp_suffix, &data)
+        .await
+        .context("failed to write checkpoint file")
+}
+
+/// The number of bytes a resumable download writes between updates of its checkpoint.
+#[cfg(feature = "download-to-path-resumable")]
+const CHECKPOINT_INTERVAL: u64 = 8 * 1024 * 1024;
+
+/// Write the body of a response to a file like [`write_response`],
+/// updating the checkpoint every [`CHECKPOINT_INTERVAL`] bytes.
+///
+/// Before the checkpoint is updated, the file data is synced,
+/// so that the checkpoint never claims more than is on disk if the process is killed.
+#[cfg(feature = "download-to-path-resumable")]
+async fn write_response_with_checkpoints(
+    response: reqwest::Response,
+    file: &mut tokio::fs::File,
+    temporary_path: &Path,
+    options: &DownloadToPathOptions,
+    checkpoint: &mut Checkpoint,
+) -> anyhow::Result<u64> {
+    let start = checkpoint.downloaded;
+    #[cfg(feature = "progress")]
+    let mut progress_bar_written = 0;
+    let mut synced = 0;
+    let after_chunk: &mut AfterChunkFn<'_, tokio::fs::File> = &mut |file, written| {
+        #[cfg(feature = "progress")]
+        if let Some(progress_bar) = options.progress_bar.as_ref() {
+            progress_bar.inc(written - progress_bar_written);
+            progress_bar_written = written;
+        }
+
+        if written - synced < CHECKPOINT_INTERVAL {
+            return Box::pin(async { Ok(()) });
+        }
+        synced = written;
+        checkpoint.downloaded = start + written;
+
+        // The future can only borrow the file, so it gets its own copy of the rest.
+        let checkpoint = checkpoint.clone();
+        let temporary_path = temporary_path.to_path_buf();
+        let temp_suffix = options.temp_suffix.clone();
+        Box::pin(async move {
+            file.sync_data().await.context("failed to sync file data")?;
+            write_checkpoint(&temporary_path, &temp_suffix, &checkpoint).await
+        })
+    };
+
+    write_response_to_file(
+        response,
+        file,
+        options.read_timeout,
+        options.progress_channel.as_ref(),
+        None,
+        Some(after_chunk),
+    )
+    .await
+}
+
+/// Write the body of a response to a file, advancing the progress bar of the options if there is one.
+async fn write_response(
+    response: reqwest::Response,
+    file: &mut tokio::fs::File,
+    options: &DownloadToPathOptions,
+) -> anyhow::Result<u64> {
+    #[cfg(feature = "progress")]
+    if let Some(progress_bar) = options.progress_bar.as_ref() {
+        return write_response_to_file_with_progress(
+            response,
+            file,
+            options.read_timeout,
+            options.progress_channel.as_ref(),
+            progress_bar,
+        )
+        .await;
+    }
+
+    write_response_to_file(
+        response,
+        file,
+        options.read_timeout,
+        options.progress_channel.as_ref(),
+        None,
+        None,
+    )
+    .await
+}
+
+/// Send a request, failing if the response headers take longer than the header timeout of the options.
+///
+/// If the options pin a certificate, the certificate of the response is checked as well.
+async fn send_request(
+    request: reqwest::RequestBuilder,
+    options: &DownloadToPathOptions,
+) -> anyhow::Result<reqwest::Response> {
+    let response = match options.header_timeout {
+        Some(timeout) => tokio::time::timeout(timeout, request.send())
+            .await
+            .context("timed out getting headers")?,
+        None => request.send().await,
+    };
+    let response = response.context("failed to get headers")?;
+
+    #[cfg(feature = "download-to-path-pinned")]
+    if let Some(expected) = options.certificate_fingerprint.as_deref() {
+        use sha2::Digest;
+
+        let peer_certificate = response
+            .extensions()
+            .get::<reqwest::tls::TlsInfo>()
+            .and_then(|tls_info| tls_info.peer_certificate())
+            .context("the response was not sent over TLS")?;
+        let fingerprint: String = sha2::Sha256::digest(peer_certificate)
+            .iter()
+            .map(|byte| format!("{byte:02x}"))
+            .collect();
+        ensure!(
+            fingerprint == expected,
+            "the server certificate fingerprint {fingerprint} does not match the pinned fingerprint {expected}"
+        );
+    }
+
+    Ok(response)
+}
+
+/// Write to a locked temporary file next to the given path, renaming it to the path on success.
+///
+/// See [`download_to_path`] for details on the temporary file.
+///
+/// # Returns
+/// Returns the number of bytes that were written.
+async fn write_via_temporary_file<F>(
+    path: &Path,
+    temp_suffix: &str,
+    write: F,
+) -> anyhow::Result<u64>
+where
+    F: AsyncFnOnce(&mut tokio::fs::File) -> anyhow::Result<u64>,
+{
+    // Create temporary path.
+    let temporary_path = with_push_extension(path, temp_suffix);
+
+    // Setup to open the temporary file.
+    //
+    // We do NOT use mandatory locking on Windows.
+    // This is because the file would need to be dropped to be renamed,
+    // which leads to a race as we must release ALL locks to do so.
+    //
+    // TODO: On linux, consider probing for O_TMPFILE support somehow and create an unnamed tmp file and use linkat.
+    let mut open_options = tokio::fs::OpenOptions::new();
+    open_options.write(true);
+
+    // If we don't have a mechanism to prevent stomping,
+    // at least ensure that we can't stomp.
+    cfg_if! {
+        if #[cfg(any(windows, unix))] {
+            // We prevent stomping by locking somehow.
+            // Create and overwrite the temporary file.
+            open_options.create(true);
+        } else {
+            // If the temporary file exists, return an error.
+            open_options.create_new(true);
+        }
+    }
+
+    // Open the temporary file.
+    let temporary_file = open_options
+        .open(&temporary_path)
+        .await
+        .context("failed to create temporary file")?;
+
+    // Create the remove handle for the temporary path.
+    let mut temporary_path = DropRemovePath::new(temporary_path);
+
+    let result = async {
+        // Wrap the file in a lock, if the platform supports it.
+        cfg_if! {
+            if #[cfg(any(unix, windows))] {
+                let mut temporary_file_lock = crate::AsyncFileLock::new(temporary_file);
+                let mut temporary_file = temporary_file_lock.try_lock().context("failed to lock temporary file")?;
+            } else {
+                let mut temporary_file = temporary_file;
+            }
+        }
+
+        // Write the file.
+        let bytes = write(&mut temporary_file).await?;
+
+        // Perform rename from temporary file path to actual file path.
+        tokio::fs::rename(&temporary_path, &path)
+            .await
+            .context("failed to rename temporary file")?;
+
+        // Ensure that the file handle is dropped AFTER we rename.
+        //
+        // Uwrap the file from the file lock.
+        cfg_if! {
+            if #[cfg(any(unix, windows))] {
+                // Unlock lock.
+                drop(temporary_file);
+
+                // Get file from lock.
+                let temporary_file = temporary_file_lock.into_inner();
+            }
+        }
+
+        drop(temporary_file.into_std());
+
+        Ok(bytes)
+    }
+    .await;
+
+    match result.as_ref() {
+        Ok(_) => {
+            // Persist the file,
+            // since it was renamed and we don't want to remove a non-existent file.
+            temporary_path.persist();
+        }
+        Err(_error) => {
+            // Try to clean up the temporary file before returning.
+            if let Err((mut temporary_path, error)) = temporary_path.try_drop().await {
+                // Don't try to delete the file again.
+                temporary_path.persist();
+
+                // Returning the original error is more important,
+                // so we just log the temporary file error here.
+                warn!("failed to delete temporary file '{error}'");
+            }
+        }
+    }
+
+    result
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_server::response;
+    use crate::test_server::TestServer;
+
+    #[cfg(feature = "download-to-path-pinned")]
+    const PINNED_CERT: &[u8] = include_bytes!("../test_data/pinned_cert.der");
+    #[cfg(feature = "download-to-path-pinned")]
+    const PINNED_KEY: &[u8] = include_bytes!("../test_data/pinned_key.der");
+    #[cfg(feature = "download-to-path-pinned")]
+    const OTHER_CERT: &[u8] = include_bytes!("../test_data/other_cert.der");
+    #[cfg(feature = "download-to-path-pinned")]
+    const OTHER_KEY: &[u8] = include_bytes!("../test_data/other_key.der");
+
+    /// Get the fingerprint of a certificate, in the format of [`DownloadToPathOptions::pin_certificate_fingerprint`].
+    #[cfg(feature = "download-to-path-pinned")]
+    fn fingerprint(cert_der: &[u8]) -> String {
+        use sha2::Digest;
+
+        sha2::Sha256::digest(cert_der)
+            .iter()
+            .map(|byte| format!("{byte:02X}"))
+            .collect::<Vec<_>>()
+            .join(":")
+    }
+
+    /// Make a client builder that trusts the given self-signed certificate.
+    #[cfg(feature = "download-to-path-pinned")]
+    fn trusting_client_builder(cert_der: &[u8]) -> reqwest::ClientBuilder {
+        let certificate = reqwest::Certificate::from_der(cert_der).expect("invalid certificate");
+        reqwest::Client::builder().add_root_certificate(certificate)
+    }
+
+    #[tokio::test]
+    async fn it_works() {
+        tokio::fs::create_dir_all("test_tmp")
+            .await
+            .expect("failed to create tmp dir");
+
+        let client = reqwest::Client::new();
+        download_to_path(&client, "http://google.com", "test_tmp/google.html")
+            .await
+            .expect("failed to download");
+    }
+
+    #[test]
+    fn blocking_works() {
+        std::fs::create_dir_all("test_tmp").expect("failed to create tmp dir");
+
+        let server = TestServer::spawn([response("200 OK", &[], b"data")]);
+        let url = format!("{}/file", server.url);
+        let path = Path::new("test_tmp/download_to_path_blocking.txt");
+
+        let runtime = tokio::runtime::Builder::new_multi_thread()
+            .enable_all()
+            .build()
+            .expect("failed to build runtime");
+        let client = reqwest::Client::new();
+        download_to_path_blocking(runtime.handle(), &client, &url, path)
+            .expect("failed to download");
+        assert!(std::fs::read(path).expect("failed to read") == b"data");
+
+        server.join();
+    }
+
+    #[tokio::test]
+    async fn etag_unchanged() {
+        tokio::fs::create_dir_all("test_tmp")
+            .await
+            .expect("failed to create tmp dir");
+
+        let server = TestServer::spawn([
+            response("200 OK", &["ETag: \"1\""], b"data"),
+            response("304 Not Modified", &[], b""),
+        ]);
+        let url = format!("{}/file", server.url);
+        let path = Path::new("test_tmp/download_to_path_etag.txt");
+
+        let client = reqwest::Client::new();
+        let options = DownloadToPathOptions::new().etag("\"1\"");
+        let outcome = download_to_path_with_options(&client, &url, path, options.clone())
+            .await
+            .expect("failed to download");
+        assert!(matches!(
+            outcome,
+            DownloadOutcome::Downloaded {
+                bytes: 4,
+                last_modified: None,
+                ref etag,
+                content_type: None,
+                ..
+            } if etag.as_deref() == Some("\"1\"")
+        ));
+        assert!(outcome.bytes_per_second().is_some_and(|speed| speed > 0.0));
+
+        let outcome = download_to_path_with_options(&client, &url, path, options)
+            .await
+            .expect("failed to download");
+        assert!(outcome == DownloadOutcome::Unchanged);
+        assert!(std::fs::read(path).expect("failed to read") == b"data");
+
+        let requests = server.join();
+        assert!(requests[1].contains("if-none-match: \"1\""));
+
+        let options = DownloadToPathOptions::new().overwrite(false);
+        let outcome = download_to_path_with_options(&client, &url, path, options)
+            .await
+            .expect("failed to download");
+        assert!(outcome == DownloadOutcome::Skipped);
+    }
+
+    #[tokio::test]
+    async fn last_modified_unchanged() {
+        tokio::fs::create_dir_all("test_tmp")
+            .await
+            .expect("failed to create tmp dir");
+
+        let server = TestServer::spawn([
+            response(
+                "200 OK",
+                &["Last-Modified: Wed, 21 Oct 2015 07:28:00 GMT"],
+                b"data",
+            ),
+            response("304 Not Modified", &[], b""),
+        ]);
+        let url = format!("{}/file", server.url);
+        let path = Path::new("test_tmp/download_to_path_last_modified.txt");
+
+        let client = reqwest::Client::new();
+        let outcome =
+            download_to_path_with_options(&client, &url, path, DownloadToPathOptions::new())
+                .await
+                .expect("failed to download");
+        let DownloadOutcome::Downloaded {
+            last_modified: Some(last_modified),
+            ..
+        } = outcome
+        else {
+            panic!("unexpected outcome {outcome:?}");
+        };
+        assert!(last_modified == SystemTime::UNIX_EPOCH + Duration::from_secs(1445412480));
+
+        let options = DownloadToPathOptions::new().last_modified(last_modified);
+        let outcome = download_to_path_with_options(&client, &url, path, options)
+            .await
+            .expect("failed to download");
+        assert!(outcome == DownloadOutcome::Unchanged);
+
+        let requests = server.join();
+        assert!(requests[1].contains("if-modified-since: Wed, 21 Oct 2015 07:28:00 GMT"));
+    }
+
+    #[cfg(feature = "download-to-path-metadata")]
+    #[tokio::test]
+    async fn metadata_works() {
+        tokio::fs::create_dir_all("test_tmp")
+            .await
+            .expect("failed to create tmp dir");
+
+        let server = TestServer::spawn([response(
+            "200 OK",
+            &["Content-Type: text/plain", "ETag: \"1\""],
+            b"data",
+        )]);
+        let url = format!("{}/file", server.url);
+        let path = Path::new("test_tmp/download_to_path_metadata.txt");
+
+        let client = reqwest::Client::new();
+        let options = DownloadToPathOptions::new().write_metadata(true);
+        download_to_path_with_options(&client, &url, path, options)
+            .await
+            .expect("failed to download");
+
+        let metadata = std::fs::read("test_tmp/download_to_path_metadata.txt.meta")
+            .expect("failed to read metadata");
+        let metadata: crate::DownloadMetadata =
+            serde_json::from_slice(&metadata).expect("failed to parse metadata");
+        assert!(metadata.url == url);
+        assert!(metadata.content_length == 4);
+        assert!(metadata.content_type.as_deref() == Some("text/plain"));
+        assert!(metadata.etag.as_deref() == Some("\"1\""));
+
+        server.join();
+    }
+
+    #[cfg(feature = "progress")]
+    #[tokio::test]
+    async fn progress_works() {
+        tokio::fs::create_dir_all("test_tmp")
+            .await
+            .expect("failed to create tmp dir");
+
+        let server = TestServer::spawn([response("200 OK", &[], b"data")]);
+        let url = format!("{}/file", server.url);
+        let path = Path::new("test_tmp/download_to_path_progress.txt");
+
+        let client = reqwest::Client::new();
+        let progress_bar = indicatif::ProgressBar::hidden();
+        let options = DownloadToPathOptions::new().with_progress_bar(progress_bar.clone());
+        download_to_path_with_options(&client, &url, path, options)
+            .await
+            .expect("failed to download");
+        assert!(progress_bar.length() == Some(4));
+        assert!(progress_bar.position() == 4);
+
+        server.join();
+    }
+
+    #[cfg(feature = "sha256")]
+    #[tokio::test]
+    async fn skip_if_hash_matches_works() {
+        use sha2::Digest;
+
+        tokio::fs::create_dir_all("test_tmp")
+            .await
+            .expect("failed to create tmp dir");
+
+        let server = TestServer::spawn([response("200 OK", &[], b"new data")]);
+        let url = format!("{}/file", server.url);
+        let path = Path::new("test_tmp/download_to_path_skip_if_hash_matches.txt");
+        tokio::fs::write(path, b"data")
+            .await
+            .expect("failed to write file");
+
+        // The server would only answer this with new data.
+        let client = reqwest::Client::new();
+        let expected = sha2::Sha256::digest(b"data").into();
+        let options = DownloadToPathOptions::new().skip_if_hash_matches(expected);
+        let outcome = download_to_path_with_options(&client, &url, path, options.clone())
+            .await
+            .expect("failed to download");
+        assert!(outcome == DownloadOutcome::Unchanged);
+
+        let options = DownloadToPathOptions::new().skip_if_hash_matches([0; 32]);
+        let outcome = download_to_path_with_options(&client, &url, path, options)
+            .await
+            .expect("failed to download");
+        assert!(matches!(
+            outcome,
+            DownloadOutcome::Downloaded { bytes: 8, .. }
+        ));
+        assert!(std::fs::read(path).expect("failed to read") == b"new data");
+
+        assert!(server.join().len() == 1);
+    }
+
+    #[cfg(feature = "sha256")]
+    #[tokio::test]
+    async fn write_sha256_sidecar_works() {
+        tokio::fs::create_dir_all("test_tmp")
+            .await
+            .expect("failed to create tmp dir");
+
+        let server = TestServer::spawn([response("200 OK", &[], b"data")]);
+        let url = format!("{}/file", server.url);
+        let path = Path::new("test_tmp/download_to_path_write_sha256_sidecar.txt");
+
+        let client = reqwest::Client::new();
+        let options = DownloadToPathOptions::new().write_sha256_sidecar(true);
+        download_to_path_with_options(&client, &url, path, options)
+            .await
+            .expect("failed to download");
+
+        let sidecar =
+            std::fs::read_to_string("test_tmp/download_to_path_write_sha256_sidecar.txt.sha256")
+                .expect("failed to read sidecar");
+        assert!(
+            sidecar
+                == "3a6eb0790f39ac87c94f3856b2dd2c5d110e6811602261a9a923d3bb23adc8b7  download_to_path_write_sha256_sidecar.txt\n"
+        );
+
+        server.join();
+    }
+
+    #[tokio::test]
+    async fn progress_channel_works() {
+        tokio::fs::create_dir_all("test_tmp")
+            .await
+            .expect("failed to create tmp dir");
+
+        let server = TestServer::spawn([response("200 OK", &[], b"data")]);
+        let url = format!("{}/file", server.url);
+        let path = Path::new("test_tmp/download_to_path_progress_channel.txt");
+
+        let client = reqwest::Client::new();
+        let (tx, mut rx) = mpsc::channel(16);
+        let receiver = tokio::spawn(async move {
+            let mut updates = Vec::new();
+            while let Some(update) = rx.recv().await {
+                updates.push(update);
+            }
+            updates
+        });
+        let options = DownloadToPathOptions::new().progress_channel(tx);
+        download_to_path_with_options(&client, &url, path, options)
+            .await
+            .expect("failed to download");
+
+        let updates = tokio::time::timeout(Duration::from_secs(5), receiver)
+            .await
+            .expect("timed out waiting for task")
+            .expect("failed to join task");
+        assert!(
+            updates.last()
+                == Some(&DownloadProgress {
+                    downloaded: 4,
+                    total: Some(4),
+                })
+        );
+
+        server.join();
+    }
+
+    #[tokio::test]
+    async fn allowed_content_types_works() {
+        tokio::fs::create_dir_all("test_tmp")
+            .await
+            .expect("failed to create tmp dir");
+
+        let server = TestServer::spawn([
+            response("200 OK", &["Content-Type: text/html"], b"<html>"),
+            response(
+                "200 OK",
+                &["Content-Type: Text/Plain; charset=utf-8"],
+                b"data",
+            ),
+        ]);
+        let url = format!("{}/file", server.url);
+        let path = Path::new("test_tmp/download_to_path_allowed_content_types.txt");
+        let _ = std::fs::remove_file(path);
+
+        let client = reqwest::Client::new();
+        let options =
+            DownloadToPathOptions::new().allowed_content_types(&["text/plain", "text/csv"]);
+        download_to_path_with_options(&client, &url, path, options.clone())
+            .await
+            .expect_err("downloaded wrong type");
+        assert!(!path.exists());
+        assert!(!Path::new("test_tmp/download_to_path_allowed_content_types.txt.part").exists());
+
+        download_to_path_with_options(&client, &url, path, options)
+            .await
+            .expect("failed to download");
+        assert!(std::fs::read(path).expect("failed to read") == b"data");
+
+        server.join();
+    }
+
+    #[tokio::test]
+    async fn completion_notify_works() {
+        tokio::fs::create_dir_all("test_tmp")
+            .await
+            .expect("failed to create tmp dir");
+
+        let server = TestServer::spawn([response("404 Not Found", &[], b"")]);
+        let url = format!("{}/file", server.url);
+        let path = Path::new("test_tmp/download_to_path_completion_notify.txt");
+
+        let notify = Arc::new(Notify::new());
+        let waiter = tokio::spawn({
+            let notify = notify.clone();
+            async move { notify.notified().await }
+        });
+
+        // Failed downloads are done too.
+        let client = reqwest::Client::new();
+        let options = DownloadToPathOptions::new().completion_notify(notify);
+        download_to_path_with_options(&client, &url, path, options)
+            .await
+            .expect_err("downloaded a missing file");
+        tokio::time::timeout(Duration::from_secs(5), waiter)
+            .await
+            .expect("notify was not woken")
+            .expect("failed to join task");
+
+        server.join();
+    }
+
+    #[tokio::test]
+    async fn header_timeout_works() {
+        tokio::fs::create_dir_all("test_tmp")
+            .await
+            .expect("failed to create tmp dir");
+
+        // This server connects right away, but is slow to respond.
+        let server = TestServer::spawn_with(1, |_head| {
+            std::thread::sleep(Duration::from_millis(500));
+            response("200 OK", &[], b"data")
+        });
+        let url = format!("{}/file", server.url);
+        let path = Path::new("test_tmp/download_to_path_header_timeout.txt");
+
+        let client = reqwest::Client::new();
+        let options = DownloadToPathOptions::new().header_timeout(Duration::from_millis(50));
+        let error = download_to_path_with_options(&client, &url, path, options)
+            .await
+            .expect_err("did not time out");
+        assert!(error.to_string().contains("timed out getting headers"));
+        assert!(!path.exists());
+
+        server.join();
+    }
+
+    #[tokio::test]
+    async fn read_timeout_works() {
+        tokio::fs::create_dir_all("test_tmp")
+            .await
+            .expect("failed to create tmp dir");
+
+        // This server sends half of the body, then stalls.
+        let server = TestServer::spawn_stalled(
+            b"HTTP/1.1 200 OK\r\nContent-Length: 8\r\nConnection: close\r\n\r\ndata".to_vec(),
+            Duration::from_millis(500),
+        );
+        let url = format!("{}/file", server.url);
+        let path = Path::new("test_tmp/download_to_path_read_timeout.txt");
+
+        let client = reqwest::Client::new();
+        let options = DownloadToPathOptions::new().read_timeout(Duration::from_millis(50));
+        let error = download_to_path_with_options(&client, &url, path, options)
+            .await
+            .expect_err("did not time out");
+        assert!(format!("{error:#}").contains("timed out getting next chunk"));
+        assert!(!path.exists());
+
+        server.join();
+    }
+
+    #[test]
+    fn temp_suffix_works() {
+        assert!(DownloadToPathOptions::new().temp_suffix(".tmp").temp_suffix == "tmp");
+
+        // An empty suffix would make the temporary path the final path.
+        for suffix in ["", "."] {
+            std::panic::catch_unwind(|| DownloadToPathOptions::new().temp_suffix(suffix))
+                .expect_err("accepted an empty temp suffix");
+        }
+    }
+
+    #[test]
+    fn content_disposition_filename_works() {
+        let filename = |value: &'static str| {
+            let mut headers = reqwest::header::HeaderMap::new();
+            headers.insert(CONTENT_DISPOSITION, HeaderValue::from_static(value));
+            content_disposition_filename(&headers)
+        };
+
+        assert!(filename("attachment; filename=\"foo.zip\"").as_deref() == Some("foo.zip"));
+        assert!(filename("attachment; filename=foo.zip").as_deref() == Some("foo.zip"));
+        assert!(
+            filename("attachment; size=1; filename=foo.zip; x=\"y\"").as_deref() == Some("foo.zip")
+        );
+        assert!(
+            filename("attachment; filename=\"a \\\"b\\\".zip\"; size=1").as_deref()
+                == Some("a \"b\".zip")
+        );
+        assert!(
+            filename("attachment; filename=\"foo.zip\"; filename*=UTF-8''f%C3%B6%C3%B6.zip")
+                .as_deref()
+                == Some("föö.zip")
+        );
+        assert!(
+            filename("attachment; filename=\"v1..2.tar.gz\"").as_deref() == Some("v1..2.tar.gz")
+        );
+        assert!(filename("attachment; filename=\"../../etc/passwd\"").is_none());
+        assert!(filename("attachment; filename=\"dir\\\\foo.zip\"").is_none());
+        assert!(filename("attachment; filename*=UTF-8''a%2Fb.zip").is_none());
+        assert!(filename("attachment; filename=\"..\"").is_none());
+        assert!(filename("attachment; filename=\" \"").is_none());
+        assert!(filename("attachment").is_none());
+    }
+
+    #[tokio::test]
+    async fn download_to_dir_uses_content_disposition() {
+        let dir = Path::new("test_tmp/download_to_dir");
+        tokio::fs::create_dir_all(dir)
+            .await
+            .expect("failed to create tmp dir");
+
+        let server = TestServer::spawn([
+            response("200 OK", &[], b"data"),
+            response(
+                "200 OK",
+                &["Content-Disposition: attachment; filename=\"named.txt\""],
+                b"named data",
+            ),
+        ]);
+        let url = format!("{}/file.txt", server.url);
+
+        let client = reqwest::Client::new();
+        let options = DownloadToPathOptions::new().use_content_disposition_filename(true);
+        let outcome = download_to_dir(&client, &url, dir, options.clone())
+            .await
+            .expect("failed to download");
+        assert!(matches!(
+            outcome,
+            DownloadOutcome::Downloaded { ref path, .. } if path == &dir.join("file.txt")
+        ));
+
+        let outcome = download_to_dir(&client, &url, dir, options)
+            .await
+            .expect("failed to download");
+        assert!(matches!(
+            outcome,
+            DownloadOutcome::Downloaded { ref path, .. } if path == &dir.join("named.txt")
+        ));
+        assert!(std::fs::read(dir.join("named.txt")).expect("failed to read") == b"named data");
+
+        server.join();
+    }
+
+    #[test]
+    fn url_file_name_works() {
+        let file_name = |url: &str| url_file_name(&reqwest::Url::parse(url).unwrap());
+
+        assert!(file_name("http://example.com/dir/a%20b.txt").unwrap() == "a b.txt");
+        assert!(file_name("http://example.com/").unwrap() == "download");
+        assert!(file_name("http://example.com").unwrap() == "download");
+        file_name("http://example.com/a%2F..%2Fb.txt").expect_err("accepted a separator");
+        file_name("http://example.com/a%5Cb.txt").expect_err("accepted a separator");
+        assert!(file_name("http://example.com/v1..2.tar.gz").unwrap() == "v1..2.tar.gz");
+        assert!(file_name("http://example.com/a%2E%2Eb").unwrap() == "a..b");
+        file_name("http://example.com/%FF").expect_err("accepted invalid UTF-8");
+    }
+
+    #[cfg(feature = "download-to-path-resumable")]
+    #[tokio::test]
+    async fn resumable_works() {
+        tokio::fs::create_dir_all("test_tmp")
+            .await
+            .expect("failed to create tmp dir");
+
+        // The first response is cut off after 4 of 8 bytes.
+        let server = TestServer::spawn([
+            b"HTTP/1.1 200 OK\r\nETag: \"1\"\r\nContent-Length: 8\r\nConnection: close\r\n\r\ndata"
+                .to_vec(),
+            response(
+                "206 Partial Content",
+                &["ETag: \"1\"", "Content-Range: bytes 4-7/8"],
+                b"more",
+            ),
+        ]);
+        let url = format!("{}/file", server.url);
+        let path = Path::new("test_tmp/download_to_path_resumable.txt");
+        let checkpoint_path = Path::new("test_tmp/download_to_path_resumable.txt.part.checkpoint");
+        let _ = std::fs::remove_file(checkpoint_path);
+
+        let client = reqwest::Client::new();
+        let options = DownloadToPathOptions::new().resumable(true);
+        download_to_path_with_options(&client, &url, path, options.clone())
+            .await
+            .expect_err("downloaded a cut off file");
+        let checkpoint: Checkpoint =
+            serde_json::from_slice(&std::fs::read(checkpoint_path).expect("missing checkpoint"))
+                .expect("invalid checkpoint");
+        assert!(checkpoint.downloaded == 4);
+        assert!(checkpoint.total == Some(8));
+
+        let outcome = download_to_path_with_options(&client, &url, path, options)
+            .await
+            .expect("failed to resume");
+        assert!(matches!(
+            outcome,
+            DownloadOutcome::Downloaded { bytes: 8, .. }
+        ));
+        assert!(std::fs::read(path).expect("failed to read") == b"datamore");
+        assert!(!checkpoint_path.exists(), "checkpoint was not removed");
+
+        let requests = server.join();
+        assert!(requests[1].contains("range: bytes=4-"));
+        assert!(requests[1].contains("if-range: \"1\""));
+    }
+
+    #[cfg(feature = "download-to-path-resumable")]
+    #[tokio::test]
+    async fn resumable_checkpoints_while_downloading() {
+        tokio::fs::create_dir_all("test_tmp")
+            .await
+            .expect("failed to create tmp dir");
+
+        let total = usize::try_from(CHECKPOINT_INTERVAL * 2).unwrap();
+        let body: Vec<u8> = (0..total).map(|i| (i % 251) as u8).collect();
+
+        // The first response stalls after a bit more than one checkpoint interval.
+        // The second response sends the rest, from wherever the range starts.
+        let server = TestServer::spawn_with_stall(2, Duration::from_secs(1), {
+            let body = body.clone();
+            move |head| {
+                let Some(range) = head
+                    .lines()
+                    .find_map(|line| line.strip_prefix("range: bytes="))
+                else {
+                    let mut response = format!(
+                        "HTTP/1.1 200 OK\r\nETag: \"1\"\r\nContent-Length: {total}\r\nConnection: close\r\n\r\n"
+                    )
+                    .into_bytes();
+                    response.extend(&body[..total / 2 + 1000]);
+                    return response;
+                };
+                let start: usize = range.trim_end_matches('-').parse().unwrap();
+                response(
+                    "206 Partial Content",
+                    &[
+                        "ETag: \"1\"",
+                        &format!("Content-Range: bytes {start}-{}/{total}", total - 1),
+                    ],
+                    &body[start..],
+                )
+            }
+        });
+        let url = format!("{}/file", server.url);
+        let path = Path::new("test_tmp/download_to_path_resumable_checkpoints.txt");
+        let checkpoint_path =
+            Path::new("test_tmp/download_to_path_resumable_checkpoints.txt.part.checkpoint");
+        let _ = std::fs::remove_file(checkpoint_path);
+
+        // Kill the download once a checkpoint was written during the body.
+        let client = reqwest::Client::new();
+        let options = DownloadToPathOptions::new().resumable(true);
+        let task = tokio::spawn({
+            let client = client.clone();
+            let url = url.clone();
+            let options = options.clone();
+            async move { download_to_path_with_options(&client, &url, path, options).await }
+        });
+        let checkpoint = tokio::time::timeout(Duration::from_secs(5), async {
+            loop {
+                let checkpoint = std::fs::read(checkpoint_path)
+                    .ok()
+                    .and_then(|data| serde_json::from_slice::<Checkpoint>(&data).ok());
+                if let Some(checkpoint) = checkpoint.filter(|checkpoint| checkpoint.downloaded > 0)
+                {
+                    break checkpoint;
+                }
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .expect("no checkpoint was written while downloading");
+        task.abort();
+        let _ = tokio::time::timeout(Duration::from_secs(5), task)
+            .await
+            .expect("timed out waiting for task");
+        // Chunks don't line up with the interval, so the checkpoint may be a bit past it.
+        assert!(checkpoint.downloaded >= CHECKPOINT_INTERVAL);
+        assert!(checkpoint.downloaded <= u64::try_from(total / 2 + 1000).unwrap());
+
+        download_to_path_with_options(&client, &url, path, options)
+            .await
+            .expect("failed to resume");
+        assert!(std::fs::read(path).expect("failed to read") == body);
+        assert!(!checkpoint_path.exists(), "checkpoint was not removed");
+
+        let requests = server.join();
+        assert!(requests[1].contains(&format!("range: bytes={}-", checkpoint.downloaded)));
+    }
+
+    #[cfg(feature = "download-to-path-resumable")]
+    #[tokio::test]
+    async fn resumable_removes_mismatched_checkpoint() {
+        tokio::fs::create_dir_all("test_tmp")
+            .await
+            .expect("failed to create tmp dir");
+
+        // The server resumes from the wrong place, then sends the whole file.
+        let server = TestServer::spawn([
+            b"HTTP/1.1 200 OK\r\nContent-Length: 8\r\nConnection: close\r\n\r\ndata".to_vec(),
+            response(
+                "206 Partial Content",
+                &["Content-Range: bytes 2-7/8"],
+                b"tamore",
+            ),
+            response("200 OK", &[], b"datamore"),
+        ]);
+        let url = format!("{}/file", server.url);
+        let path = Path::new("test_tmp/download_to_path_resumable_mismatch.txt");
+        let checkpoint_path =
+            Path::new("test_tmp/download_to_path_resumable_mismatch.txt.part.checkpoint");
+        let _ = std::fs::remove_file(checkpoint_path);
+
+        let client = reqwest::Client::new();
+        let options = DownloadToPathOptions::new().resumable(true);
+        download_to_path_with_options(&client, &url, path, options.clone())
+            .await
+            .expect_err("downloaded a cut off file");
+        assert!(checkpoint_path.exists(), "checkpoint was not written");
+
+        download_to_path_with_options(&client, &url, path, options.clone())
+            .await
+            .expect_err("resumed from the wrong place");
+        assert!(!checkpoint_path.exists(), "mismatched checkpoint was kept");
+
+        // Without the checkpoint, the next attempt starts over.
+        download_to_path_with_options(&client, &url, path, options)
+            .await
+            .expect("failed to download");
+        assert!(std::fs::read(path).expect("failed to read") == b"datamore");
+
+        let requests = server.join();
+        assert!(!requests[2].contains("range:"));
+    }
+
+    #[tokio::test]
+    async fn method_and_body_work() {
+        tokio::fs::create_dir_all("test_tmp")
+            .await
+            .expect("failed to create tmp dir");
+
+        let server = TestServer::spawn([response("200 OK", &[], b"data")]);
+        let url = format!("{}/file", server.url);
+        let path = Path::new("test_tmp/download_to_path_method.txt");
+
+        let client = reqwest::Client::new();
+        let options = DownloadToPathOptions::new()
+            .method(Method::POST)
+            .body("query");
+        download_to_path_parallel(&client, &url, path, 4, options)
+            .await
+            .expect("failed to download");
+        assert!(std::fs::read(path).expect("failed to read") == b"data");
+
+        let requests = server.join();
+        assert!(requests[0].starts_with("POST /file"));
+        assert!(requests[0].contains("content-length: 5"));
+    }
+
+    #[cfg(feature = "download-to-path-pinned")]
+    #[tokio::test]
+    async fn pinned_accepts_matching_certificate() {
+        tokio::fs::create_dir_all("test_tmp")
+            .await
+            .expect("failed to create tmp dir");
+
+        let server =
+            TestServer::spawn_tls([response("200 OK", &[], b"data")], PINNED_CERT, PINNED_KEY);
+        let url = format!("{}/file", server.url);
+        let path = Path::new("test_tmp/download_to_path_pinned_match.txt");
+
+        // The settings of the builder, like its roots, are kept.
+        let options =
+            DownloadToPathOptions::new().pin_certificate_fingerprint(&fingerprint(PINNED_CERT));
+        download_to_path_with_client_builder(
+            trusting_client_builder(PINNED_CERT),
+            &url,
+            path,
+            options,
+        )
+        .await
+        .expect("failed to download");
+        assert!(std::fs::read(path).expect("failed to read") == b"data");
+
+        server.join();
+    }
+
+    #[cfg(feature = "download-to-path-pinned")]
+    #[tokio::test]
+    async fn pinned_rejects_mismatched_certificate() {
+        tokio::fs::create_dir_all("test_tmp")
+            .await
+            .expect("failed to create tmp dir");
+
+        // The other certificate is trusted, so the handshake succeeds and only the pin fails.
+        let server =
+            TestServer::spawn_tls([response("200 OK", &[], b"data")], OTHER_CERT, OTHER_KEY);
+        let url = format!("{}/file", server.url);
+        let path = Path::new("test_tmp/download_to_path_pinned_mismatch.txt");
+
+        let options =
+            DownloadToPathOptions::new().pin_certificate_fingerprint(&fingerprint(PINNED_CERT));
+        let error = download_to_path_with_client_builder(
+            trusting_client_builder(OTHER_CERT),
+            &url,
+            path,
+            options,
+        )
+        .await
+        .expect_err("accepted a mismatched certificate");
+        assert!(error.to_string().contains("does not match"));
+        assert!(!path.exists());
+
+        server.join();
+    }
+
+    #[cfg(feature = "download-to-path-pinned")]
+    #[tokio::test]
+    async fn pinned_rejects_plain_http() {
+        let server = TestServer::spawn([response("200 OK", &[], b"data")]);
+        let url = format!("{}/file", server.url);
+        let path = Path::new("test_tmp/download_to_path_pinned.txt");
+
+        let options = DownloadToPathOptions::new().pin_certificate_fingerprint("AB:CD");
+        let error =
+            download_to_path_with_client_builder(reqwest::Client::builder(), &url, path, options)
+                .await
+                .expect_err("accepted a response without TLS");
+        assert!(error.to_string().contains("not sent over TLS"));
+        assert!(!path.exists());
+
+        server.join();
+
+        // An existing client doesn't report certificates, so pinning needs a builder.
+        let client = reqwest::Client::new();
+        let options = DownloadToPathOptions::new().pin_certificate_fingerprint("AB:CD");
+        let error = download_to_path_with_options(&client, &url, path, options)
+            .await
+            .expect_err("pinned a certificate without a builder");
+        assert!(error
+            .to_string()
+            .contains("download_to_path_with_client_builder"));
+    }
+
+    #[tokio::test]
+    async fn proxy_works() {
+        tokio::fs::create_dir_all("test_tmp")
+            .await
+            .expect("failed to create tmp dir");
+
+        let server = TestServer::spawn([response("200 OK", &[], b"data")]);
+        let path = Path::new("test_tmp/download_to_path_proxy.txt");
+
+        let proxy = reqwest::Proxy::http(&server.url).expect("invalid proxy");
+        let options = DownloadToPathOptions::new().proxy(proxy);
+        download_to_path_with_client_builder(
+            reqwest::Client::builder(),
+            "http://example.invalid/file",
+            path,
+            options,
+        )
+        .await
+        .expect("failed to download");
+        assert!(std::fs::read(path).expect("failed to read") == b"data");
+
+        let requests = server.join();
+        assert!(requests[0].starts_with("GET http://example.invalid/file HTTP/1.1"));
+
+        // The proxy can't be applied to an existing client.
+        let client = reqwest::Client::new();
+        let proxy = reqwest::Proxy::http("http://127.0.0.1:1").expect("invalid proxy");
+        let options = DownloadToPathOptions::new().proxy(proxy);
+        let error =
+            download_to_path_with_options(&client, "http://example.invalid/file", path, options)
+                .await
+                .expect_err("ignored the proxy");
+        assert!(error
+            .to_string()
+            .contains("download_to_path_with_client_builder"));
+    }
+
+    #[tokio::test]
+    async fn fallback_works() {
+        tokio::fs::create_dir_all("test_tmp")
+            .await
+            .expect("failed to create tmp dir");
+
+        let server = TestServer::spawn([
+            response("500 Internal Server Error", &[], b""),
+            response("200 OK", &[], b"data"),
+        ]);
+        let first_url = format!("{}/first", server.url);
+        let second_url = format!("{}/second", server.url);
+        let path = Path::new("test_tmp/download_to_path_fallback.txt");
+
+        let client = reqwest::Client::new();
+        download_to_path_with_fallback(
+            &client,
+            &[&first_url, &second_url],
+            path,
+            DownloadToPathOptions::new(),
+        )
+        .await
+        .expect("failed to download");
+        assert!(std::fs::read(path).expect("failed to read") == b"data");
+
+        let requests = server.join();
+        assert!(requests[0].starts_with("GET /first"));
+        assert!(requests[1].starts_with("GET /second"));
+
+        download_to_path_with_fallback(&client, &[], path, DownloadToPathOptions::new())
+            .await
+            .expect_err("downloaded without urls");
+    }
+
+    #[tokio::test]
+    async fn parallel_works() {
+        tokio::fs::create_dir_all("test_tmp")
+            .await
+            .expect("failed to create tmp dir");
+
+        let data = b"0123456789";
+        let server = TestServer::spawn_with(4, move |head| {
+            if head.starts_with("HEAD") {
+                let content_length = format!("Content-Length: {}", data.len());
+                let head = format!(
+                    "HTTP/1.1 200 OK\r\nAccept-Ranges: bytes\r\n{content_length}\r\nConnection: close\r\n\r\n"
+                );
+                return head.into_bytes();
+            }
+
+            let range = head
+                .lines()
+                .find_map(|line| line.strip_prefix("range: bytes="))
+                .expect("missing range");
+            let (start, end) = range.split_once('-').expect("invalid range");
+            let start: usize = start.parse().expect("invalid start");
+            let end: usize = end.parse().expect("invalid end");
+            let content_range = format!("Content-Range: bytes {start}-{end}/{}", data.len());
+            response("206 Partial Content", &[&content_range], &data[start..=end])
+        });
+        let url = format!("{}/file", server.url);
+        let path = Path::new("test_tmp/download_to_path_parallel.txt");
+
+        let client = reqwest::Client::new();
+        let outcome =
+            download_to_path_parallel(&client, &url, path, 3, DownloadToPathOptions::new())
+                .await
+                .expect("failed to download");
+        assert!(matches!(
+            outcome,
+            DownloadOutcome::Downloaded {
+                bytes: 10,
+                last_modified: None,
+                etag: None,
+                content_type: None,
+                ..
+            }
+        ));
+        assert!(std::fs::read(path).expect("failed to read") == data);
+        for index in 0..3 {
+            let segment_path = format!("test_tmp/download_to_path_parallel.txt.part.{index}");
+            assert!(!Path::new(&segment_path).exists(), "segment file exists");
+        }
+
+        let requests = server.join();
+        assert!(requests
+            .iter()
+            .any(|head| head.contains("range: bytes=6-9")));
     }
 }
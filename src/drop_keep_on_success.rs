@@ -0,0 +1,118 @@
+use std::ops::Deref;
+use std::path::Path;
+use std::path::PathBuf;
+
+/// Asyncronously remove a file at a path on drop, but only on failure.
+///
+/// This is the inverse of [`DropRemovePath`](crate::DropRemovePath).
+/// By default, the file is kept when this is dropped.
+/// The file is removed on drop if [`DropKeepOnSuccess::on_error`] was called,
+/// or if the thread is panicking, like when the task holding this panics.
+///
+/// Currently, this only supports files, NOT directories.
+#[derive(Debug)]
+pub struct DropKeepOnSuccess {
+    /// The path
+    path: PathBuf,
+
+    /// Whether dropping this should remove the file.
+    should_remove: bool,
+}
+
+impl DropKeepOnSuccess {
+    /// Make a new [`DropKeepOnSuccess`].
+    pub fn new<P>(path: P) -> Self
+    where
+        P: AsRef<Path>,
+    {
+        Self {
+            path: path.as_ref().into(),
+            should_remove: false,
+        }
+    }
+
+    /// Mark the operation as failed, removing the file at this path on drop.
+    pub fn on_error(&mut self) {
+        self.should_remove = true;
+    }
+}
+
+impl AsRef<Path> for DropKeepOnSuccess {
+    fn as_ref(&self) -> &Path {
+        self.path.as_ref()
+    }
+}
+
+impl Deref for DropKeepOnSuccess {
+    type Target = Path;
+
+    fn deref(&self) -> &Self::Target {
+        &self.path
+    }
+}
+
+impl Drop for DropKeepOnSuccess {
+    fn drop(&mut self) {
+        // We need to check this here, as the spawned task will not be panicking.
+        let panicking = std::thread::panicking();
+        if !self.should_remove && !panicking {
+            return;
+        }
+        let path = std::mem::take(&mut self.path);
+
+        // Try to remove the path.
+        tokio::spawn(async move {
+            if let Err(error) = tokio::fs::remove_file(path).await {
+                let message = format!("failed to delete file: '{error}'");
+                if panicking {
+                    eprintln!("{message}");
+                } else {
+                    panic!("{message}");
+                }
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[tokio::test]
+    async fn drop_keep_on_success_sanity_check() {
+        tokio::fs::create_dir_all("test_tmp")
+            .await
+            .expect("failed to create tmp dir");
+
+        let file_path: &Path = "test_tmp/drop_keep_on_success.txt".as_ref();
+        let file_data = b"testing 1 2 3";
+
+        tokio::fs::write(file_path, file_data)
+            .await
+            .expect("failed to write file");
+        let guard = tokio::spawn(async move {
+            let _guard = DropKeepOnSuccess::new(file_path);
+        });
+        tokio::time::timeout(std::time::Duration::from_secs(5), guard)
+            .await
+            .expect("timed out waiting for task")
+            .expect("failed to join task");
+        assert!(file_path.exists(), "successful file does not exist");
+
+        let guard = tokio::spawn(async move {
+            let _guard = DropKeepOnSuccess::new(file_path);
+            panic!("task failed");
+        });
+        assert!(guard.await.is_err());
+
+        // Give the removal task a chance to run.
+        for _ in 0..1000 {
+            if !file_path.exists() {
+                break;
+            }
+            tokio::task::yield_now().await;
+            std::thread::sleep(std::time::Duration::from_millis(1));
+        }
+        assert!(!file_path.exists(), "failed file exists");
+    }
+}
@@ -0,0 +1,96 @@
+use std::ops::Deref;
+use std::path::Path;
+use std::path::PathBuf;
+
+/// Remove a file at a path on drop, but only on failure.
+///
+/// This is the inverse of a guard that removes unless persisted.
+/// By default, the file is kept when this is dropped.
+/// The file is removed on drop if [`DropKeepOnSuccessBlocking::on_error`] was called,
+/// or if the thread is panicking.
+///
+/// Currently, this only supports files, NOT directories.
+#[derive(Debug)]
+pub struct DropKeepOnSuccessBlocking {
+    /// The path
+    path: PathBuf,
+
+    /// Whether dropping this should remove the file.
+    should_remove: bool,
+}
+
+impl DropKeepOnSuccessBlocking {
+    /// Make a new [`DropKeepOnSuccessBlocking`].
+    pub fn new<P>(path: P) -> Self
+    where
+        P: AsRef<Path>,
+    {
+        Self {
+            path: path.as_ref().into(),
+            should_remove: false,
+        }
+    }
+
+    /// Mark the operation as failed, removing the file at this path on drop.
+    pub fn on_error(&mut self) {
+        self.should_remove = true;
+    }
+}
+
+impl AsRef<Path> for DropKeepOnSuccessBlocking {
+    fn as_ref(&self) -> &Path {
+        self.path.as_ref()
+    }
+}
+
+impl Deref for DropKeepOnSuccessBlocking {
+    type Target = Path;
+
+    fn deref(&self) -> &Self::Target {
+        &self.path
+    }
+}
+
+impl Drop for DropKeepOnSuccessBlocking {
+    fn drop(&mut self) {
+        let panicking = std::thread::panicking();
+        if !self.should_remove && !panicking {
+            return;
+        }
+
+        if let Err(error) = std::fs::remove_file(&self.path) {
+            let message = format!("failed to delete file: '{error}'");
+            if panicking {
+                eprintln!("{message}");
+            } else {
+                panic!("{message}");
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn drop_keep_on_success_sanity_check() {
+        std::fs::create_dir_all("test_tmp").expect("failed to create tmp dir");
+
+        let file_path: &Path = "test_tmp/drop_keep_on_success_blocking.txt".as_ref();
+        let file_data = b"testing 1 2 3";
+
+        {
+            std::fs::write(file_path, file_data).expect("failed to write file");
+            let _guard = DropKeepOnSuccessBlocking::new(file_path);
+        }
+        assert!(file_path.exists(), "successful file does not exist");
+
+        {
+            std::fs::write(file_path, file_data).expect("failed to write file");
+            let mut guard = DropKeepOnSuccessBlocking::new(file_path);
+            guard.on_error();
+        }
+        assert!(!file_path.exists(), "failed file exists");
+    }
+}
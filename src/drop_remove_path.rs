@@ -1,57 +1,288 @@
-use std::mem::ManuallyDrop;
+use crate::remove_mode::check_file_name_prefix;
+use crate::remove_mode::CleanupFn;
+#[cfg(feature = "drop-remove-path-blocking")]
+use crate::remove_mode::GuardParts;
+use crate::remove_mode::RemoveMode;
+use std::future::Future;
 use std::ops::Deref;
 use std::path::Path;
 use std::path::PathBuf;
+use std::pin::Pin;
+use std::sync::Arc;
+
+/// An async function that cleans up a path, instead of removing it.
+type AsyncCleanupFn = dyn FnOnce(PathBuf) -> Pin<Box<dyn Future<Output = ()> + Send>> + Send + Sync;
 
 /// Asyncronously remove a file at a path on drop.
 ///
-/// Currently, this only supports files, NOT directories.
-#[derive(Debug)]
+/// By default, this only supports files.
+/// Use [`DropRemovePath::new_recursive`] to remove a directory tree instead.
 pub struct DropRemovePath {
     /// The path
     path: PathBuf,
 
     /// Whether dropping this should remove the file.
     should_remove: bool,
+
+    /// How the path should be removed.
+    mode: RemoveMode,
+
+    /// A prefix that the file name must start with for the path to be removed.
+    required_prefix: Option<String>,
+
+    /// The function to call instead of removing the path.
+    cleanup: Option<Box<CleanupFn>>,
+
+    /// The async function to call instead of removing the path.
+    async_cleanup: Option<Box<AsyncCleanupFn>>,
 }
 
 impl DropRemovePath {
     /// Make a new [`DropRemovePath`].
     pub fn new<P>(path: P) -> Self
+    where
+        P: AsRef<Path>,
+    {
+        Self::new_with_remove(path, true)
+    }
+
+    /// Make a new [`DropRemovePath`], specifying whether the file should be removed on drop.
+    ///
+    /// Passing `false` is the same as calling [`DropRemovePath::persist`] after construction.
+    pub fn new_with_remove<P>(path: P, remove: bool) -> Self
+    where
+        P: AsRef<Path>,
+    {
+        Self {
+            path: path.as_ref().into(),
+            should_remove: remove,
+            mode: RemoveMode::File,
+            required_prefix: None,
+            cleanup: None,
+            async_cleanup: None,
+        }
+    }
+
+    /// Make a new [`DropRemovePath`] for a directory.
+    ///
+    /// On drop, the directory and all of its contents are removed.
+    pub fn new_recursive<P>(path: P) -> Self
     where
         P: AsRef<Path>,
     {
         Self {
             path: path.as_ref().into(),
             should_remove: true,
+            mode: RemoveMode::Dir,
+            required_prefix: None,
+            cleanup: None,
+            async_cleanup: None,
+        }
+    }
+
+    /// Make a new [`DropRemovePath`] that only removes the file if its file name starts with the given prefix.
+    ///
+    /// This guards against accidentally removing the wrong file,
+    /// like when the path was built from the wrong variable.
+    ///
+    /// # Panics
+    /// Removing the file, either on drop or through [`DropRemovePath::try_drop`],
+    /// panics if the file name does not start with the prefix.
+    pub fn new_safe<P>(path: P, prefix: &str) -> Self
+    where
+        P: AsRef<Path>,
+    {
+        Self {
+            path: path.as_ref().into(),
+            should_remove: true,
+            mode: RemoveMode::File,
+            required_prefix: Some(prefix.into()),
+            cleanup: None,
+            async_cleanup: None,
+        }
+    }
+
+    /// Make a new [`DropRemovePath`] that runs the given async function instead of removing the path.
+    ///
+    /// This is for paths that need more than removal to clean up, like unmounting a directory or telling a server.
+    /// On drop, the future is spawned as a task, like the removal task of other guards.
+    /// Through [`DropRemovePath::try_drop`], the future is awaited instead.
+    /// Since the future has no result, it is up to the cleanup function to report any errors.
+    pub fn with_async_cleanup<P, F, Fut>(path: P, cleanup: F) -> Self
+    where
+        P: AsRef<Path>,
+        F: FnOnce(PathBuf) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        Self {
+            path: path.as_ref().into(),
+            should_remove: true,
+            mode: RemoveMode::File,
+            required_prefix: None,
+            cleanup: None,
+            async_cleanup: Some(Box::new(move |path| Box::pin(cleanup(path)))),
+        }
+    }
+
+    /// Make a new [`DropRemovePath`] from its parts.
+    #[cfg(feature = "drop-remove-path-blocking")]
+    pub(crate) fn from_parts(parts: GuardParts) -> Self {
+        Self {
+            path: parts.path,
+            should_remove: parts.should_remove,
+            mode: parts.mode,
+            required_prefix: parts.required_prefix,
+            cleanup: parts.cleanup,
+            async_cleanup: None,
+        }
+    }
+
+    /// Split this into its parts, without removing the path.
+    ///
+    /// An async cleanup function becomes a blocking one that spawns it on the current runtime.
+    ///
+    /// # Panics
+    /// Panics if there is an async cleanup function and this is not called from within a tokio runtime.
+    #[cfg(feature = "drop-remove-path-blocking")]
+    pub(crate) fn into_parts(mut self) -> GuardParts {
+        let cleanup = match self.async_cleanup.take() {
+            Some(async_cleanup) => {
+                let handle = tokio::runtime::Handle::current();
+                let mut async_cleanup = Some(async_cleanup);
+                let cleanup: Box<CleanupFn> = Box::new(move |path: &Path| {
+                    if let Some(async_cleanup) = async_cleanup.take() {
+                        handle.spawn(async_cleanup(path.into()));
+                    }
+                    Ok(())
+                });
+                Some(cleanup)
+            }
+            None => self.cleanup.take(),
+        };
+
+        GuardParts {
+            path: std::mem::take(&mut self.path),
+            should_remove: std::mem::replace(&mut self.should_remove, false),
+            mode: self.mode,
+            required_prefix: self.required_prefix.take(),
+            cleanup,
         }
     }
 
+    /// Convert this into a [`DropRemovePathBlocking`](crate::DropRemovePathBlocking).
+    ///
+    /// This is useful for moving the guard into a blocking context, like a `spawn_blocking` closure.
+    /// A cleanup function from [`DropRemovePath::with_async_cleanup`] is kept,
+    /// and is spawned on the runtime that this was called from when the blocking guard is dropped.
+    ///
+    /// # Panics
+    /// Panics if this has an async cleanup function and is not called from within a tokio runtime.
+    #[cfg(feature = "drop-remove-path-blocking")]
+    pub fn into_blocking(self) -> crate::DropRemovePathBlocking {
+        crate::DropRemovePathBlocking::from_parts(self.into_parts())
+    }
+
     /// Persist the file at this path.
     pub fn persist(&mut self) {
         self.should_remove = false;
     }
 
+    /// Make a new [`DropRemovePath`] for the same path, which never removes it.
+    ///
+    /// This guard can't be cloned, as both guards would try to remove the path.
+    /// Instead, this gives a guard that only refers to the path, while this guard stays in charge of removing it.
+    pub fn clone_no_remove(&self) -> DropRemovePath {
+        Self {
+            path: self.path.clone(),
+            should_remove: false,
+            mode: self.mode,
+            required_prefix: self.required_prefix.clone(),
+            cleanup: None,
+            async_cleanup: None,
+        }
+    }
+
+    /// Persist the file at this path, consuming this and returning the path.
+    ///
+    /// This is for when the guard is no longer needed, but the file should be kept.
+    pub fn forget(mut self) -> PathBuf {
+        self.should_remove = false;
+        std::mem::take(&mut self.path)
+    }
+
+    /// Rename the file at this path to a new path, persisting it.
+    ///
+    /// This is the async version of [`DropRemovePathBlocking::rename_to`](crate::DropRemovePathBlocking::rename_to).
+    /// If the rename fails, this is dropped, removing the file at the old path in the background if needed.
+    ///
+    /// # Return
+    /// Returns the new path.
+    pub async fn rename_to(mut self, new_path: &Path) -> std::io::Result<PathBuf> {
+        tokio::fs::rename(&self.path, new_path).await?;
+
+        // The file is no longer at this path, so don't spawn a task to remove it on drop.
+        self.should_remove = false;
+
+        Ok(new_path.into())
+    }
+
     /// Try to drop this file path, removing it if needed.
     ///
     /// # Return
     /// Returns an error if the file could not be removed.
     /// Returns Ok(true) if the file was removed.
     /// Returns Ok(false) if the file was not removed.
-    pub async fn try_drop(self) -> Result<bool, (Self, std::io::Error)> {
-        let wrapper = ManuallyDrop::new(self);
-        let should_remove = wrapper.should_remove;
+    pub async fn try_drop(mut self) -> Result<bool, (Self, std::io::Error)> {
+        let should_remove = self.should_remove;
 
         if should_remove {
-            tokio::fs::remove_file(&wrapper.path)
-                .await
-                .map_err(|e| (ManuallyDrop::into_inner(wrapper), e))?;
+            check_file_name_prefix(&self.path, self.required_prefix.as_deref());
+            // Keep a blocking cleanup if it fails, so that dropping the returned guard tries it again.
+            let result = match (self.cleanup.as_mut(), self.async_cleanup.take()) {
+                (_, Some(async_cleanup)) => {
+                    async_cleanup(self.path.clone()).await;
+                    Ok(())
+                }
+                (Some(cleanup), None) => cleanup(&self.path),
+                (None, None) => self.mode.remove_async(&self.path).await,
+            };
+            if let Err(error) = result {
+                return Err((self, error));
+            }
         }
 
+        // The path is gone, so don't try to remove it again on drop.
+        self.should_remove = false;
+
         Ok(should_remove)
     }
 }
 
+impl std::fmt::Debug for DropRemovePath {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("DropRemovePath")
+            .field("path", &self.path)
+            .field("should_remove", &self.should_remove)
+            .field("mode", &self.mode)
+            .field("required_prefix", &self.required_prefix)
+            .field("cleanup", &self.cleanup.as_ref().map(|_| "..."))
+            .field("async_cleanup", &self.async_cleanup.as_ref().map(|_| "..."))
+            .finish()
+    }
+}
+
+// This matches `DropRemovePathBlocking`, like "temp file at /tmp/foo.part".
+impl std::fmt::Display for DropRemovePath {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let kind = match self.mode {
+            RemoveMode::File => "file",
+            RemoveMode::Dir => "directory",
+        };
+
+        write!(f, "temp {kind} at {}", self.path.display())
+    }
+}
+
 impl AsRef<Path> for DropRemovePath {
     fn as_ref(&self) -> &Path {
         self.path.as_ref()
@@ -68,25 +299,90 @@ impl Deref for DropRemovePath {
 
 impl Drop for DropRemovePath {
     fn drop(&mut self) {
-        let should_remove = self.should_remove;
+        // Don't spawn a task if there is nothing to do.
+        if !self.should_remove {
+            return;
+        }
+        if !check_file_name_prefix(&self.path, self.required_prefix.as_deref()) {
+            return;
+        }
+        if let Some(cleanup) = self.cleanup.as_mut() {
+            if let Err(error) = cleanup(&self.path) {
+                let message = format!("failed to clean up path: '{error}'");
+                if std::thread::panicking() {
+                    eprintln!("{message}");
+                } else {
+                    panic!("{message}");
+                }
+            }
+            return;
+        }
         let path = std::mem::take(&mut self.path);
+        if let Some(async_cleanup) = self.async_cleanup.take() {
+            tokio::spawn(async_cleanup(path));
+            return;
+        }
+        let mode = self.mode;
 
         // Try to remove the path.
         tokio::spawn(async move {
-            if should_remove {
-                if let Err(error) = tokio::fs::remove_file(path).await {
-                    let message = format!("failed to delete file: '{error}'");
-                    if std::thread::panicking() {
-                        eprintln!("{message}");
-                    } else {
-                        panic!("{message}");
-                    }
+            if let Err(error) = mode.remove_async(&path).await {
+                let message = format!("failed to delete path: '{error}'");
+                if std::thread::panicking() {
+                    eprintln!("{message}");
+                } else {
+                    panic!("{message}");
                 }
             }
         });
     }
 }
 
+/// A [`DropRemovePath`] with shared ownership.
+///
+/// This is useful when several tasks need a path that should only be removed once all of them are done.
+/// Clones share the same guard, which removes the path when the last clone is dropped.
+/// Since this relies on the [`Arc`] dropping the guard, there is no race between clones dropping at the same time.
+#[derive(Debug, Clone)]
+pub struct SharedDropRemovePath(Arc<DropRemovePath>);
+
+impl SharedDropRemovePath {
+    /// Make a new [`SharedDropRemovePath`] from a guard.
+    pub fn new(guard: DropRemovePath) -> Self {
+        Self(Arc::new(guard))
+    }
+
+    /// Get the inner guard back, if this is the only clone.
+    ///
+    /// This can be used to persist the path or to call [`DropRemovePath::try_drop`] once all other users are done.
+    ///
+    /// # Return
+    /// Returns `Err(self)` if there are other clones.
+    pub fn try_unwrap(self) -> Result<DropRemovePath, Self> {
+        Arc::try_unwrap(self.0).map_err(Self)
+    }
+}
+
+impl From<DropRemovePath> for SharedDropRemovePath {
+    fn from(guard: DropRemovePath) -> Self {
+        Self::new(guard)
+    }
+}
+
+impl AsRef<Path> for SharedDropRemovePath {
+    fn as_ref(&self) -> &Path {
+        self.0.as_ref()
+    }
+}
+
+impl Deref for SharedDropRemovePath {
+    type Target = Path;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -145,4 +441,248 @@ mod test {
         // Failed cleanup does not matter
         let _ = tokio::fs::remove_file(file_path).await.is_ok();
     }
+
+    #[tokio::test]
+    async fn rename_to_works() {
+        tokio::fs::create_dir_all("test_tmp")
+            .await
+            .expect("failed to create tmp dir");
+
+        let file_path: &Path = "test_tmp/drop_remove_path_rename_to.txt.tmp".as_ref();
+        let new_file_path: &Path = "test_tmp/drop_remove_path_rename_to.txt".as_ref();
+
+        tokio::fs::write(file_path, "data")
+            .await
+            .expect("failed to write file");
+        let path = DropRemovePath::new(file_path)
+            .rename_to(new_file_path)
+            .await
+            .expect("failed to rename");
+        assert!(path == new_file_path);
+        assert!(!file_path.exists(), "old file exists");
+        assert!(new_file_path.exists(), "new file does not exist");
+    }
+
+    #[tokio::test]
+    async fn clone_no_remove_works() {
+        tokio::fs::create_dir_all("test_tmp")
+            .await
+            .expect("failed to create tmp dir");
+
+        let file_path: &Path = "test_tmp/drop_remove_path_clone_no_remove.txt".as_ref();
+        tokio::fs::write(file_path, "data")
+            .await
+            .expect("failed to write file");
+
+        let drop_remove_path = DropRemovePath::new(file_path);
+        let clone = drop_remove_path.clone_no_remove();
+        assert!(&*clone == file_path);
+        assert!(!clone.try_drop().await.expect("failed to drop"));
+        assert!(file_path.exists(), "clone removed file");
+
+        assert!(drop_remove_path
+            .try_drop()
+            .await
+            .expect("failed to remove file"));
+        assert!(!file_path.exists(), "nonpersisted file exists");
+    }
+
+    #[tokio::test]
+    async fn with_async_cleanup_works() {
+        let file_path: &Path = "test_tmp/drop_remove_path_with_async_cleanup.txt".as_ref();
+
+        // On drop, the cleanup runs as a task.
+        let (tx, rx) = std::sync::mpsc::channel();
+        drop(DropRemovePath::with_async_cleanup(file_path, {
+            let tx = tx.clone();
+            async move |path| {
+                let _ = tx.send(path);
+            }
+        }));
+        let path = tokio::time::timeout(std::time::Duration::from_secs(5), async {
+            loop {
+                if let Ok(path) = rx.try_recv() {
+                    break path;
+                }
+                tokio::task::yield_now().await;
+            }
+        })
+        .await
+        .expect("cleanup did not run");
+        assert!(path == file_path);
+
+        // Through try_drop, the cleanup is awaited.
+        let removed = DropRemovePath::with_async_cleanup(file_path, async move |path| {
+            let _ = tx.send(path);
+        })
+        .try_drop()
+        .await
+        .expect("failed to clean up");
+        assert!(removed);
+        assert!(rx.try_recv().expect("cleanup did not run") == file_path);
+    }
+
+    #[test]
+    fn display_works() {
+        let path = DropRemovePath::new_with_remove("test_tmp/foo.part", false);
+        assert!(path.to_string() == "temp file at test_tmp/foo.part");
+
+        let mut path = DropRemovePath::new_recursive("test_tmp/foo");
+        path.persist();
+        assert!(path.to_string() == "temp directory at test_tmp/foo");
+    }
+
+    #[tokio::test]
+    async fn forget_works() {
+        tokio::fs::create_dir_all("test_tmp")
+            .await
+            .expect("failed to create tmp dir");
+
+        let file_path: &Path = "test_tmp/drop_remove_path_forget.txt".as_ref();
+        tokio::fs::write(file_path, "data")
+            .await
+            .expect("failed to write file");
+
+        let path = DropRemovePath::new(file_path).forget();
+        assert!(path == file_path);
+
+        // Give a removal task the chance to run, if one was wrongly spawned.
+        tokio::task::yield_now().await;
+        assert!(file_path.exists(), "forgotten file does not exist");
+    }
+
+    #[tokio::test]
+    async fn shared_removes_on_last_drop() {
+        tokio::fs::create_dir_all("test_tmp")
+            .await
+            .expect("failed to create tmp dir");
+
+        let file_path: &Path = "test_tmp/shared_drop_remove_path.txt".as_ref();
+        tokio::fs::write(file_path, "data")
+            .await
+            .expect("failed to write file");
+
+        let shared = SharedDropRemovePath::new(DropRemovePath::new(file_path));
+        let tasks: Vec<_> = (0..4)
+            .map(|_| {
+                let shared = shared.clone();
+                tokio::spawn(async move {
+                    assert!(shared.exists(), "shared file does not exist");
+                })
+            })
+            .collect();
+        for task in tasks {
+            tokio::time::timeout(std::time::Duration::from_secs(5), task)
+                .await
+                .expect("timed out waiting for task")
+                .expect("failed to join task");
+        }
+        assert!(file_path.exists(), "shared file does not exist");
+
+        let clone = shared.clone();
+        let shared = shared
+            .try_unwrap()
+            .expect_err("unwrapped with other clones");
+        drop(clone);
+        shared
+            .try_unwrap()
+            .expect("failed to unwrap last clone")
+            .try_drop()
+            .await
+            .expect("failed to remove file");
+        assert!(!file_path.exists(), "nonpersisted file exists");
+    }
+
+    #[cfg(feature = "drop-remove-path-blocking")]
+    #[tokio::test]
+    async fn into_blocking_round_trip() {
+        tokio::fs::create_dir_all("test_tmp")
+            .await
+            .expect("failed to create tmp dir");
+
+        let dir_path: &Path = "test_tmp/drop_remove_path_into_blocking".as_ref();
+        tokio::fs::create_dir_all(dir_path.join("nested"))
+            .await
+            .expect("failed to create dir");
+
+        let drop_remove_path = DropRemovePath::new_recursive(dir_path);
+        let task = tokio::task::spawn_blocking(move || {
+            let drop_remove_path = drop_remove_path.into_blocking();
+            assert!(&*drop_remove_path == dir_path);
+            drop_remove_path.into_async()
+        });
+        tokio::time::timeout(std::time::Duration::from_secs(5), task)
+            .await
+            .expect("timed out waiting for task")
+            .expect("failed to join task")
+            .try_drop()
+            .await
+            .expect("failed to remove dir");
+
+        assert!(!dir_path.exists(), "nonpersisted dir exists");
+    }
+
+    #[cfg(feature = "drop-remove-path-blocking")]
+    #[tokio::test]
+    async fn try_drop_keeps_failed_cleanup() {
+        tokio::fs::create_dir_all("test_tmp")
+            .await
+            .expect("failed to create tmp dir");
+
+        let file_path: &Path =
+            "test_tmp/drop_remove_path_try_drop_keeps_failed_cleanup.txt".as_ref();
+        tokio::fs::write(file_path, "data")
+            .await
+            .expect("failed to write file");
+
+        // Another user holds the lock, so the cleanup fails.
+        let other = std::fs::File::open(file_path).expect("failed to open file");
+        other.lock().expect("failed to lock file");
+        let drop_remove_path =
+            crate::DropRemovePathBlocking::with_exclusive_lock_retries(file_path, 0).into_async();
+        let (drop_remove_path, _) = drop_remove_path
+            .try_drop()
+            .await
+            .expect_err("removed a locked file");
+
+        // The cleanup is kept, so it still respects the lock when trying again.
+        let (drop_remove_path, _) = drop_remove_path
+            .try_drop()
+            .await
+            .expect_err("removed a locked file");
+        assert!(file_path.exists(), "locked file was removed");
+
+        other.unlock().expect("failed to unlock file");
+        drop(other);
+        assert!(drop_remove_path
+            .try_drop()
+            .await
+            .expect("failed to remove file"));
+        assert!(!file_path.exists(), "nonpersisted file exists");
+    }
+
+    #[tokio::test]
+    async fn safe_checks_prefix() {
+        tokio::fs::create_dir_all("test_tmp")
+            .await
+            .expect("failed to create tmp dir");
+
+        let file_path: &Path = "test_tmp/safe_drop_remove_path.txt".as_ref();
+        tokio::fs::write(file_path, "data")
+            .await
+            .expect("failed to write file");
+
+        let result = std::panic::catch_unwind(|| {
+            drop(DropRemovePath::new_safe(file_path, "other_"));
+        });
+        assert!(result.is_err(), "removed file without prefix");
+        assert!(file_path.exists(), "file without prefix was removed");
+
+        let removed = DropRemovePath::new_safe(file_path, "safe_")
+            .try_drop()
+            .await
+            .expect("failed to remove file");
+        assert!(removed);
+        assert!(!file_path.exists(), "nonpersisted file exists");
+    }
 }
@@ -0,0 +1,653 @@
+use crate::remove_mode::check_file_name_prefix;
+use crate::remove_mode::CleanupFn;
+#[cfg(feature = "drop-remove-path")]
+use crate::remove_mode::GuardParts;
+use crate::remove_mode::RemoveMode;
+use std::fs::TryLockError;
+use std::ops::Deref;
+use std::path::Path;
+use std::path::PathBuf;
+use std::time::Duration;
+
+/// Remove a file at a path on drop, blocking the current thread.
+///
+/// This is the blocking version of [`DropRemovePath`](crate::DropRemovePath).
+///
+/// By default, this only supports files.
+/// Use [`DropRemovePathBlocking::new_recursive`] to remove a directory tree instead.
+pub struct DropRemovePathBlocking {
+    /// The path
+    path: PathBuf,
+
+    /// Whether dropping this should remove the file.
+    should_remove: bool,
+
+    /// How the path should be removed.
+    mode: RemoveMode,
+
+    /// A prefix that the file name must start with for the path to be removed.
+    required_prefix: Option<String>,
+
+    /// The function to call instead of removing the path.
+    cleanup: Option<Box<CleanupFn>>,
+
+    /// The handle that holds a lock on the file, if it is locked.
+    lock: Option<std::fs::File>,
+}
+
+impl DropRemovePathBlocking {
+    /// Make a new [`DropRemovePathBlocking`].
+    pub fn new<P>(path: P) -> Self
+    where
+        P: AsRef<Path>,
+    {
+        Self::new_with_remove(path, true)
+    }
+
+    /// Make a new [`DropRemovePathBlocking`], specifying whether the file should be removed on drop.
+    ///
+    /// Passing `false` is the same as calling [`DropRemovePathBlocking::persist`] after construction.
+    pub fn new_with_remove<P>(path: P, remove: bool) -> Self
+    where
+        P: AsRef<Path>,
+    {
+        Self {
+            path: path.as_ref().into(),
+            should_remove: remove,
+            mode: RemoveMode::File,
+            required_prefix: None,
+            cleanup: None,
+            lock: None,
+        }
+    }
+
+    /// Make a new [`DropRemovePathBlocking`] for a directory.
+    ///
+    /// On drop, the directory and all of its contents are removed.
+    pub fn new_recursive<P>(path: P) -> Self
+    where
+        P: AsRef<Path>,
+    {
+        Self {
+            path: path.as_ref().into(),
+            should_remove: true,
+            mode: RemoveMode::Dir,
+            required_prefix: None,
+            cleanup: None,
+            lock: None,
+        }
+    }
+
+    /// Make a new [`DropRemovePathBlocking`] that only removes the file if its file name starts with the given prefix.
+    ///
+    /// This guards against accidentally removing the wrong file,
+    /// like when the path was built from the wrong variable.
+    ///
+    /// # Panics
+    /// Removing the file, either on drop or through [`DropRemovePathBlocking::try_drop`],
+    /// panics if the file name does not start with the prefix.
+    pub fn new_safe<P>(path: P, prefix: &str) -> Self
+    where
+        P: AsRef<Path>,
+    {
+        Self {
+            path: path.as_ref().into(),
+            should_remove: true,
+            mode: RemoveMode::File,
+            required_prefix: Some(prefix.into()),
+            cleanup: None,
+            lock: None,
+        }
+    }
+
+    /// Make a new [`DropRemovePathBlocking`] that calls the given function on drop, instead of removing the path.
+    ///
+    /// This allows any cleanup action, like moving the file somewhere else.
+    /// The function is called with the path if and only if the path would have been removed,
+    /// so persisting the path means that it is never called.
+    /// It is also called by [`DropRemovePathBlocking::try_drop`], which then always succeeds.
+    pub fn with_cleanup<P, F>(path: P, f: F) -> Self
+    where
+        P: AsRef<Path>,
+        F: FnOnce(&Path) + Send + Sync + 'static,
+    {
+        Self {
+            path: path.as_ref().into(),
+            should_remove: true,
+            mode: RemoveMode::File,
+            required_prefix: None,
+            cleanup: Some(Box::new({
+                let mut f = Some(f);
+                move |path| {
+                    if let Some(f) = f.take() {
+                        f(path);
+                    }
+                    Ok(())
+                }
+            })),
+            lock: None,
+        }
+    }
+
+    /// Make a new [`DropRemovePathBlocking`] for a file that is locked before it is removed.
+    ///
+    /// This is for files that other processes may have open, where removing the file may fail,
+    /// like on Windows.
+    /// Before removing the file, an exclusive advisory lock is taken on it, so that other users of the lock are done with it.
+    /// If the file is already locked, this retries up to 3 times, waiting briefly between attempts.
+    /// Use [`DropRemovePathBlocking::with_exclusive_lock_retries`] to choose the number of retries.
+    pub fn with_exclusive_lock<P>(path: P) -> Self
+    where
+        P: AsRef<Path>,
+    {
+        Self::with_exclusive_lock_retries(path, DEFAULT_LOCK_RETRIES)
+    }
+
+    /// Make a new [`DropRemovePathBlocking`] for a file that is locked before it is removed, retrying locking the given number of times.
+    ///
+    /// See [`DropRemovePathBlocking::with_exclusive_lock`].
+    pub fn with_exclusive_lock_retries<P>(path: P, retries: u32) -> Self
+    where
+        P: AsRef<Path>,
+    {
+        Self {
+            path: path.as_ref().into(),
+            should_remove: true,
+            mode: RemoveMode::File,
+            required_prefix: None,
+            cleanup: Some(Box::new(move |path| remove_file_locked(path, retries))),
+            lock: None,
+        }
+    }
+
+    /// Make a new [`DropRemovePathBlocking`] for a file, taking an exclusive advisory lock on it.
+    ///
+    /// This blocks until the lock is available.
+    /// The lock is held until the file is removed or persisted, like a mutex guard,
+    /// so other users of the lock wait until this is done with the file.
+    /// When removing the file, the lock is released first, like the temporary files of `download_to_path`.
+    ///
+    /// # Errors
+    /// Returns an error if the file could not be opened or locked.
+    pub fn new_locked<P>(path: P) -> std::io::Result<Self>
+    where
+        P: AsRef<Path>,
+    {
+        let path = path.as_ref();
+        let file = std::fs::OpenOptions::new().write(true).open(path)?;
+        file.lock()?;
+
+        let mut guard = Self::new(path);
+        guard.lock = Some(file);
+
+        Ok(guard)
+    }
+
+    /// Create a new temporary dir in the given dir, and make a new [`DropRemovePathBlocking`] for it.
+    ///
+    /// The name of the dir starts with the given prefix, followed by random characters.
+    /// Like [`DropRemovePathBlocking::new_recursive`], the dir and all of its contents are removed on drop.
+    #[cfg(feature = "tempfile")]
+    pub fn from_temp_dir(base: &Path, prefix: &str) -> std::io::Result<Self> {
+        let path = tempfile::Builder::new()
+            .prefix(prefix)
+            .tempdir_in(base)?
+            .keep();
+
+        Ok(Self::new_recursive(path))
+    }
+
+    /// Make a new [`DropRemovePathBlocking`] from its parts.
+    #[cfg(feature = "drop-remove-path")]
+    pub(crate) fn from_parts(parts: GuardParts) -> Self {
+        Self {
+            path: parts.path,
+            should_remove: parts.should_remove,
+            mode: parts.mode,
+            required_prefix: parts.required_prefix,
+            cleanup: parts.cleanup,
+            lock: None,
+        }
+    }
+
+    /// Split this into its parts, without removing the path.
+    ///
+    /// The lock from [`DropRemovePathBlocking::new_locked`] is released.
+    #[cfg(feature = "drop-remove-path")]
+    pub(crate) fn into_parts(mut self) -> GuardParts {
+        self.unlock();
+
+        GuardParts {
+            path: std::mem::take(&mut self.path),
+            should_remove: std::mem::replace(&mut self.should_remove, false),
+            mode: self.mode,
+            required_prefix: self.required_prefix.take(),
+            cleanup: self.cleanup.take(),
+        }
+    }
+
+    /// Convert this into an async [`DropRemovePath`](crate::DropRemovePath).
+    ///
+    /// A cleanup function from [`DropRemovePathBlocking::with_cleanup`] is kept,
+    /// and is called by whichever thread drops the async guard.
+    /// The lock from [`DropRemovePathBlocking::new_locked`] is released.
+    #[cfg(feature = "drop-remove-path")]
+    pub fn into_async(self) -> crate::DropRemovePath {
+        crate::DropRemovePath::from_parts(self.into_parts())
+    }
+
+    /// Get the path as a [`PathBuf`].
+    ///
+    /// This is useful for APIs that specifically need a `&PathBuf`,
+    /// as [`Deref`] only gives a `&Path`.
+    pub fn as_path_buf(&self) -> &PathBuf {
+        &self.path
+    }
+
+    /// Replace the path that this manages, returning the old path.
+    ///
+    /// The old path is no longer managed, so it is up to the caller to remove it if needed.
+    /// Any lock from [`DropRemovePathBlocking::new_locked`] is on the old path, so it is released.
+    /// Whether the new path is removed on drop is unchanged.
+    pub fn swap_with<P>(&mut self, new_path: P) -> PathBuf
+    where
+        P: AsRef<Path>,
+    {
+        self.unlock();
+        std::mem::replace(&mut self.path, new_path.as_ref().into())
+    }
+
+    /// Persist the file at this path.
+    ///
+    /// This releases the lock from [`DropRemovePathBlocking::new_locked`], if there is one.
+    pub fn persist(&mut self) {
+        self.should_remove = false;
+        self.unlock();
+    }
+
+    /// Release the lock on the file, if there is one.
+    fn unlock(&mut self) {
+        if let Some(lock) = self.lock.take() {
+            // Unlocking a lock we hold should not fail, and closing the handle releases it anyways.
+            let _ = lock.unlock();
+        }
+    }
+
+    /// Persist the file at this path, consuming this and returning the path.
+    ///
+    /// This is for when the guard is no longer needed, but the file should be kept.
+    pub fn forget(mut self) -> PathBuf {
+        self.should_remove = false;
+        std::mem::take(&mut self.path)
+    }
+
+    /// Rename the file at this path to a new path, persisting it.
+    ///
+    /// This is for the common case of writing to a temporary path, then moving it into place on success.
+    /// If the rename fails, this is dropped, removing the file at the old path if needed.
+    ///
+    /// # Return
+    /// Returns the new path.
+    pub fn rename_to(mut self, new_path: &Path) -> std::io::Result<PathBuf> {
+        std::fs::rename(&self.path, new_path)?;
+
+        // The file is no longer at this path, so don't try to remove it on drop.
+        self.should_remove = false;
+
+        Ok(new_path.into())
+    }
+
+    /// Try to drop this file path, removing it if needed.
+    ///
+    /// # Return
+    /// Returns an error if the file could not be removed.
+    /// Returns Ok(true) if the file was removed.
+    /// Returns Ok(false) if the file was not removed.
+    pub fn try_drop(mut self) -> Result<bool, (Self, std::io::Error)> {
+        let should_remove = self.should_remove;
+
+        if should_remove {
+            check_file_name_prefix(&self.path, self.required_prefix.as_deref());
+            self.unlock();
+            // Keep the cleanup if it fails, so that dropping the returned guard tries it again.
+            let result = match self.cleanup.as_mut() {
+                Some(cleanup) => cleanup(&self.path),
+                None => self.mode.remove(&self.path),
+            };
+            if let Err(error) = result {
+                return Err((self, error));
+            }
+        }
+
+        // The file is gone, so don't try to remove it again on drop.
+        self.should_remove = false;
+
+        Ok(should_remove)
+    }
+}
+
+impl std::fmt::Debug for DropRemovePathBlocking {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("DropRemovePathBlocking")
+            .field("path", &self.path)
+            .field("should_remove", &self.should_remove)
+            .field("mode", &self.mode)
+            .field("required_prefix", &self.required_prefix)
+            .field("cleanup", &self.cleanup.as_ref().map(|_| "..."))
+            .field("locked", &self.lock.is_some())
+            .finish()
+    }
+}
+
+// This is for log messages, like "temp file at /tmp/foo.part".
+impl std::fmt::Display for DropRemovePathBlocking {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let kind = match self.mode {
+            RemoveMode::File => "file",
+            RemoveMode::Dir => "directory",
+        };
+
+        write!(f, "temp {kind} at {}", self.path.display())
+    }
+}
+
+/// The number of times [`DropRemovePathBlocking::with_exclusive_lock`] retries locking a file.
+const DEFAULT_LOCK_RETRIES: u32 = 3;
+
+/// How long to wait between attempts to lock a file before removing it.
+const LOCK_RETRY_DELAY: Duration = Duration::from_millis(100);
+
+/// Remove a file, taking an exclusive lock on it first.
+///
+/// If the file is locked, locking is retried the given number of times.
+fn remove_file_locked(path: &Path, retries: u32) -> std::io::Result<()> {
+    let file = std::fs::File::open(path)?;
+    let mut attempt = 0;
+    loop {
+        match file.try_lock() {
+            Ok(()) => break,
+            Err(TryLockError::WouldBlock) if attempt < retries => {
+                attempt += 1;
+                std::thread::sleep(LOCK_RETRY_DELAY);
+            }
+            Err(TryLockError::WouldBlock) => {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::WouldBlock,
+                    "the file is locked",
+                ));
+            }
+            Err(TryLockError::Error(error)) => return Err(error),
+        }
+    }
+
+    // The lock is released when the file is closed, after it is removed.
+    std::fs::remove_file(path)
+}
+
+impl AsRef<Path> for DropRemovePathBlocking {
+    fn as_ref(&self) -> &Path {
+        self.path.as_ref()
+    }
+}
+
+impl Deref for DropRemovePathBlocking {
+    type Target = Path;
+
+    fn deref(&self) -> &Self::Target {
+        &self.path
+    }
+}
+
+impl Drop for DropRemovePathBlocking {
+    fn drop(&mut self) {
+        if !self.should_remove {
+            return;
+        }
+        if !check_file_name_prefix(&self.path, self.required_prefix.as_deref()) {
+            return;
+        }
+        self.unlock();
+        let result = match self.cleanup.as_mut() {
+            Some(cleanup) => cleanup(&self.path),
+            None => self.mode.remove(&self.path),
+        };
+        if let Err(error) = result {
+            let message = format!("failed to delete path: '{error}'");
+            if std::thread::panicking() {
+                eprintln!("{message}");
+            } else {
+                panic!("{message}");
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn drop_remove_path_blocking_sanity_check() {
+        std::fs::create_dir_all("test_tmp").expect("failed to create tmp dir");
+
+        let file_path: &Path = "test_tmp/drop_remove_path_blocking.txt".as_ref();
+        let file_data = b"testing 1 2 3";
+
+        std::fs::write(file_path, file_data).expect("failed to write file");
+        let drop_remove_path = DropRemovePathBlocking::new(file_path);
+        assert!(drop_remove_path.try_drop().expect("failed to remove file"));
+        assert!(!file_path.exists(), "nonpersisted file exists");
+
+        std::fs::write(file_path, file_data).expect("failed to write file");
+        let mut drop_remove_path = DropRemovePathBlocking::new(file_path);
+        drop_remove_path.persist();
+        drop(drop_remove_path);
+        assert!(file_path.exists(), "persisted file does not exist");
+
+        let drop_remove_path = DropRemovePathBlocking::new_with_remove(file_path, false);
+        assert!(drop_remove_path.as_path_buf() == file_path);
+        assert!(!drop_remove_path.try_drop().expect("failed to drop"));
+        assert!(file_path.exists(), "persisted file does not exist");
+
+        let path = DropRemovePathBlocking::new(file_path).forget();
+        assert!(path == file_path);
+        assert!(file_path.exists(), "forgotten file does not exist");
+
+        drop(DropRemovePathBlocking::new_with_remove(file_path, true));
+        assert!(!file_path.exists(), "nonpersisted file exists");
+    }
+
+    #[test]
+    fn drop_remove_path_blocking_rename_to() {
+        std::fs::create_dir_all("test_tmp").expect("failed to create tmp dir");
+
+        let file_path: &Path = "test_tmp/drop_remove_path_blocking_rename_to.txt.tmp".as_ref();
+        let new_file_path: &Path = "test_tmp/drop_remove_path_blocking_rename_to.txt".as_ref();
+
+        std::fs::write(file_path, "data").expect("failed to write file");
+        let drop_remove_path = DropRemovePathBlocking::new(file_path);
+        let path = drop_remove_path
+            .rename_to(new_file_path)
+            .expect("failed to rename");
+        assert!(path == new_file_path);
+        assert!(!file_path.exists(), "old file exists");
+        assert!(new_file_path.exists(), "new file does not exist");
+
+        std::fs::write(file_path, "data").expect("failed to write file");
+        let drop_remove_path = DropRemovePathBlocking::new(file_path);
+        drop_remove_path
+            .rename_to("test_tmp/missing_dir/file.txt".as_ref())
+            .expect_err("renamed to missing dir");
+        assert!(!file_path.exists(), "failed file exists");
+    }
+
+    #[test]
+    fn drop_remove_path_blocking_swap_with() {
+        std::fs::create_dir_all("test_tmp").expect("failed to create tmp dir");
+
+        let first_path: &Path = "test_tmp/drop_remove_path_blocking_swap_with_1.txt".as_ref();
+        let second_path: &Path = "test_tmp/drop_remove_path_blocking_swap_with_2.txt".as_ref();
+        std::fs::write(first_path, "data").expect("failed to write file");
+        std::fs::write(second_path, "data").expect("failed to write file");
+
+        let mut drop_remove_path = DropRemovePathBlocking::new(first_path);
+        let old_path = drop_remove_path.swap_with(second_path);
+        assert!(old_path == first_path);
+        drop(drop_remove_path);
+        assert!(first_path.exists(), "old file does not exist");
+        assert!(!second_path.exists(), "nonpersisted file exists");
+    }
+
+    #[test]
+    fn drop_remove_path_blocking_recursive() {
+        let dir_path: &Path = "test_tmp/drop_remove_path_blocking_recursive".as_ref();
+
+        std::fs::create_dir_all(dir_path.join("nested")).expect("failed to create dir");
+        std::fs::write(dir_path.join("nested/file.txt"), "data").expect("failed to write file");
+
+        drop(DropRemovePathBlocking::new_recursive(dir_path));
+        assert!(!dir_path.exists(), "nonpersisted dir exists");
+    }
+
+    #[test]
+    fn drop_remove_path_blocking_safe() {
+        std::fs::create_dir_all("test_tmp").expect("failed to create tmp dir");
+
+        let file_path: &Path = "test_tmp/safe_drop_remove_path_blocking.txt".as_ref();
+        std::fs::write(file_path, "data").expect("failed to write file");
+
+        let drop_remove_path = DropRemovePathBlocking::new_safe(file_path, "safe_");
+        assert!(drop_remove_path.try_drop().expect("failed to remove file"));
+        assert!(!file_path.exists(), "nonpersisted file exists");
+
+        std::fs::write(file_path, "data").expect("failed to write file");
+        let result = std::panic::catch_unwind(|| {
+            drop(DropRemovePathBlocking::new_safe(file_path, "other_"));
+        });
+        assert!(result.is_err(), "removed file without prefix");
+        assert!(file_path.exists(), "file without prefix was removed");
+    }
+
+    #[cfg(feature = "tempfile")]
+    #[test]
+    fn drop_remove_path_blocking_from_temp_dir() {
+        std::fs::create_dir_all("test_tmp").expect("failed to create tmp dir");
+
+        let drop_remove_path =
+            DropRemovePathBlocking::from_temp_dir("test_tmp".as_ref(), "from_temp_dir")
+                .expect("failed to create temp dir");
+        let dir_path = drop_remove_path.to_path_buf();
+        assert!(dir_path.is_dir());
+        assert!(dir_path
+            .file_name()
+            .and_then(|file_name| file_name.to_str())
+            .is_some_and(|file_name| file_name.starts_with("from_temp_dir")));
+
+        std::fs::write(dir_path.join("file.txt"), "data").expect("failed to write file");
+        drop(drop_remove_path);
+        assert!(!dir_path.exists(), "nonpersisted dir exists");
+    }
+
+    #[test]
+    fn drop_remove_path_blocking_display() {
+        let mut path = DropRemovePathBlocking::new("test_tmp/foo.part");
+        path.persist();
+        assert!(path.to_string() == "temp file at test_tmp/foo.part");
+
+        let mut path = DropRemovePathBlocking::new_recursive("test_tmp/foo");
+        path.persist();
+        assert!(path.to_string() == "temp directory at test_tmp/foo");
+    }
+
+    #[test]
+    fn drop_remove_path_blocking_with_cleanup() {
+        use std::sync::atomic::AtomicBool;
+        use std::sync::atomic::Ordering;
+        use std::sync::Arc;
+
+        let file_path: &Path = "test_tmp/drop_remove_path_blocking_with_cleanup.txt".as_ref();
+
+        let called = Arc::new(AtomicBool::new(false));
+        let drop_remove_path = DropRemovePathBlocking::with_cleanup(file_path, {
+            let called = called.clone();
+            move |path| {
+                assert!(path == Path::new("test_tmp/drop_remove_path_blocking_with_cleanup.txt"));
+                called.store(true, Ordering::SeqCst);
+            }
+        });
+        drop(drop_remove_path);
+        assert!(called.load(Ordering::SeqCst), "cleanup was not called");
+
+        let called = Arc::new(AtomicBool::new(false));
+        let mut drop_remove_path = DropRemovePathBlocking::with_cleanup(file_path, {
+            let called = called.clone();
+            move |_| called.store(true, Ordering::SeqCst)
+        });
+        drop_remove_path.persist();
+        drop(drop_remove_path);
+        assert!(
+            !called.load(Ordering::SeqCst),
+            "cleanup was called for persisted path"
+        );
+    }
+
+    #[test]
+    fn drop_remove_path_blocking_with_exclusive_lock() {
+        std::fs::create_dir_all("test_tmp").expect("failed to create tmp dir");
+
+        let file_path: &Path =
+            "test_tmp/drop_remove_path_blocking_with_exclusive_lock.txt".as_ref();
+        std::fs::write(file_path, "data").expect("failed to write file");
+
+        // Another user holds the lock, so removal should give up.
+        let other = std::fs::File::open(file_path).expect("failed to open file");
+        other.lock().expect("failed to lock file");
+        let drop_remove_path = DropRemovePathBlocking::with_exclusive_lock_retries(file_path, 1);
+        let (drop_remove_path, _) = drop_remove_path
+            .try_drop()
+            .expect_err("removed a locked file");
+        assert!(file_path.exists(), "locked file was removed");
+
+        // The lock is still respected when trying again.
+        let (drop_remove_path, _) = drop_remove_path
+            .try_drop()
+            .expect_err("removed a locked file");
+        assert!(file_path.exists(), "locked file was removed");
+
+        // Once the other user is done, the file can be removed.
+        other.unlock().expect("failed to unlock file");
+        drop(other);
+        assert!(drop_remove_path.try_drop().expect("failed to remove file"));
+        assert!(!file_path.exists(), "nonpersisted file exists");
+
+        std::fs::write(file_path, "data").expect("failed to write file");
+        let drop_remove_path = DropRemovePathBlocking::with_exclusive_lock(file_path);
+        assert!(drop_remove_path.try_drop().expect("failed to remove file"));
+        assert!(!file_path.exists(), "nonpersisted file exists");
+    }
+
+    #[test]
+    fn drop_remove_path_blocking_new_locked() {
+        std::fs::create_dir_all("test_tmp").expect("failed to create tmp dir");
+
+        let file_path: &Path = "test_tmp/drop_remove_path_blocking_new_locked.txt".as_ref();
+        std::fs::write(file_path, "data").expect("failed to write file");
+        let other = std::fs::File::open(file_path).expect("failed to open file");
+
+        // The lock is held until the file is persisted.
+        let mut drop_remove_path =
+            DropRemovePathBlocking::new_locked(file_path).expect("failed to lock file");
+        other.try_lock().expect_err("locked a locked file");
+        drop_remove_path.persist();
+        other.try_lock().expect("failed to lock persisted file");
+        other.unlock().expect("failed to unlock file");
+        drop(drop_remove_path);
+        assert!(file_path.exists(), "persisted file does not exist");
+
+        let drop_remove_path =
+            DropRemovePathBlocking::new_locked(file_path).expect("failed to lock file");
+        other.try_lock().expect_err("locked a locked file");
+        drop(drop_remove_path);
+        assert!(!file_path.exists(), "nonpersisted file exists");
+    }
+}
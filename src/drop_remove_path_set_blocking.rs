@@ -0,0 +1,128 @@
+use crate::remove_mode::RemoveMode;
+use std::path::Path;
+use std::path::PathBuf;
+
+/// Remove a set of paths on drop, blocking the current thread.
+///
+/// This is like [`DropRemovePathBlocking`](crate::DropRemovePathBlocking),
+/// but for a temporary artifact that spans multiple paths, like an archive and its signature.
+/// Each path may be a file or a directory tree, and may be persisted on its own.
+#[derive(Debug, Default)]
+pub struct DropRemovePathSetBlocking {
+    /// The paths, how to remove them, and whether they should be removed on drop.
+    paths: Vec<(PathBuf, RemoveMode, bool)>,
+}
+
+impl DropRemovePathSetBlocking {
+    /// Make a new, empty [`DropRemovePathSetBlocking`].
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a path to this set.
+    ///
+    /// # Return
+    /// Returns the index of the path, for use with [`DropRemovePathSetBlocking::persist_one`].
+    pub fn push<P>(&mut self, path: P, mode: RemoveMode) -> usize
+    where
+        P: AsRef<Path>,
+    {
+        self.paths.push((path.as_ref().into(), mode, true));
+        self.paths.len() - 1
+    }
+
+    /// Get the path at the given index.
+    ///
+    /// # Return
+    /// Returns `None` if the index is out of bounds.
+    pub fn get(&self, index: usize) -> Option<&Path> {
+        self.paths.get(index).map(|(path, _, _)| path.as_path())
+    }
+
+    /// Get the number of paths in this set.
+    pub fn len(&self) -> usize {
+        self.paths.len()
+    }
+
+    /// Returns true if this set has no paths.
+    pub fn is_empty(&self) -> bool {
+        self.paths.is_empty()
+    }
+
+    /// Persist all paths in this set.
+    pub fn persist_all(&mut self) {
+        for (_, _, should_remove) in self.paths.iter_mut() {
+            *should_remove = false;
+        }
+    }
+
+    /// Persist the path at the given index.
+    ///
+    /// # Panics
+    /// Panics if the index is out of bounds.
+    pub fn persist_one(&mut self, index: usize) {
+        self.paths[index].2 = false;
+    }
+}
+
+impl Drop for DropRemovePathSetBlocking {
+    fn drop(&mut self) {
+        // Try to remove every path, even if removing one fails.
+        let mut first_error = None;
+        for (path, mode, should_remove) in self.paths.iter() {
+            if !should_remove {
+                continue;
+            }
+
+            if let Err(error) = mode.remove(path) {
+                let message = format!("failed to delete path '{}': '{error}'", path.display());
+                if std::thread::panicking() {
+                    eprintln!("{message}");
+                } else {
+                    first_error.get_or_insert(message);
+                }
+            }
+        }
+
+        if let Some(message) = first_error {
+            panic!("{message}");
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn drop_remove_path_set_blocking_sanity_check() {
+        let dir_path: &Path = "test_tmp/drop_remove_path_set_blocking".as_ref();
+        let file_path = dir_path.join("file.txt");
+        let signature_path = dir_path.join("file.txt.sig");
+        let nested_path = dir_path.join("nested");
+
+        std::fs::create_dir_all(&nested_path).expect("failed to create dir");
+        std::fs::write(&file_path, "data").expect("failed to write file");
+        std::fs::write(&signature_path, "signature").expect("failed to write file");
+        std::fs::write(nested_path.join("file.txt"), "data").expect("failed to write file");
+
+        let mut set = DropRemovePathSetBlocking::new();
+        set.push(&file_path, RemoveMode::File);
+        let signature_index = set.push(&signature_path, RemoveMode::File);
+        set.push(&nested_path, RemoveMode::Dir);
+        assert!(set.len() == 3);
+        assert!(set.get(signature_index) == Some(signature_path.as_path()));
+
+        set.persist_one(signature_index);
+        drop(set);
+        assert!(!file_path.exists(), "nonpersisted file exists");
+        assert!(signature_path.exists(), "persisted file does not exist");
+        assert!(!nested_path.exists(), "nonpersisted dir exists");
+
+        let mut set = DropRemovePathSetBlocking::new();
+        set.push(&signature_path, RemoveMode::File);
+        set.persist_all();
+        drop(set);
+        assert!(signature_path.exists(), "persisted file does not exist");
+    }
+}
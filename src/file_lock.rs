@@ -0,0 +1,108 @@
+use std::fs::File;
+use std::ops::Deref;
+use std::ops::DerefMut;
+
+/// An advisory lock on a file.
+///
+/// Locking is done with [`File::lock`], like the other advisory locks of this crate.
+/// This is only available on platforms that support advisory locking, which are currently Unix and Windows.
+/// Since the lock is advisory, it only prevents other users of advisory locks from locking the file at the same time.
+/// It does not prevent anyone from reading or writing the file.
+#[derive(Debug)]
+pub struct FileLock {
+    /// The file
+    file: File,
+}
+
+impl FileLock {
+    /// Make a new [`FileLock`] for a file.
+    ///
+    /// This does not lock the file.
+    pub fn new(file: File) -> Self {
+        Self { file }
+    }
+
+    /// Try to lock the file, without blocking.
+    ///
+    /// # Return
+    /// Returns an error if the file is already locked or could not be locked.
+    pub fn try_lock(&mut self) -> std::io::Result<FileLockGuard<'_>> {
+        self.file.try_lock()?;
+        Ok(FileLockGuard {
+            file: &mut self.file,
+        })
+    }
+
+    /// Lock the file, blocking the current thread until the lock is available.
+    ///
+    /// # Return
+    /// Returns an error if the file could not be locked.
+    pub fn lock(&mut self) -> std::io::Result<FileLockGuard<'_>> {
+        self.file.lock()?;
+        Ok(FileLockGuard {
+            file: &mut self.file,
+        })
+    }
+
+    /// Get the file back.
+    pub fn into_inner(self) -> File {
+        self.file
+    }
+}
+
+/// A guard for a locked [`FileLock`].
+///
+/// The file is unlocked when this is dropped.
+#[derive(Debug)]
+pub struct FileLockGuard<'a> {
+    /// The locked file
+    file: &'a mut File,
+}
+
+impl Deref for FileLockGuard<'_> {
+    type Target = File;
+
+    fn deref(&self) -> &Self::Target {
+        self.file
+    }
+}
+
+impl DerefMut for FileLockGuard<'_> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.file
+    }
+}
+
+impl Drop for FileLockGuard<'_> {
+    fn drop(&mut self) {
+        // Unlocking a lock we hold should not fail,
+        // and there is nothing we could do about it if it did.
+        let _ = self.file.unlock();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::io::Write;
+
+    #[test]
+    fn file_lock_sanity_check() {
+        std::fs::create_dir_all("test_tmp").expect("failed to create tmp dir");
+
+        let path = "test_tmp/file_lock.txt";
+        let mut lock = FileLock::new(File::create(path).expect("failed to create file"));
+        let mut other_lock = FileLock::new(File::open(path).expect("failed to open file"));
+
+        {
+            let mut guard = lock.try_lock().expect("failed to lock");
+            guard.write_all(b"data").expect("failed to write");
+
+            other_lock.try_lock().expect_err("locked a locked file");
+        }
+
+        other_lock.lock().expect("failed to lock unlocked file");
+        drop(lock.into_inner());
+        assert!(std::fs::read(path).expect("failed to read") == b"data");
+    }
+}
@@ -0,0 +1,58 @@
+use anyhow::Context;
+use std::path::Path;
+
+/// Serialize a value as JSON and atomically write it to a file at the given path.
+///
+/// The JSON is pretty-printed, as this is intended for config and state files.
+/// See [`write_file_atomic`](crate::write_file_atomic) for how the file is written.
+pub fn write_json_atomic<T>(path: &Path, value: &T) -> anyhow::Result<()>
+where
+    T: serde::Serialize,
+{
+    let data = serde_json::to_vec_pretty(value).context("failed to serialize json")?;
+    crate::write_file_atomic(path, &data)
+        .with_context(|| format!("failed to write \"{}\"", path.display()))?;
+
+    Ok(())
+}
+
+/// Read a file at the given path and deserialize it from JSON.
+///
+/// The path is added to the context of any error.
+pub fn read_json_file<T>(path: &Path) -> anyhow::Result<T>
+where
+    T: serde::de::DeserializeOwned,
+{
+    let data = std::fs::read_to_string(path)
+        .with_context(|| format!("failed to read \"{}\"", path.display()))?;
+    let value = serde_json::from_str(&data)
+        .with_context(|| format!("failed to parse \"{}\"", path.display()))?;
+
+    Ok(value)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn write_json_atomic_works() {
+        std::fs::create_dir_all("test_tmp").expect("failed to create tmp dir");
+
+        let path = Path::new("test_tmp/write_json_atomic.json");
+        let value = BTreeMap::from([("key", 1)]);
+        write_json_atomic(path, &value).expect("failed to write");
+
+        let round_trip: BTreeMap<String, u32> = read_json_file(path).expect("failed to read");
+        assert!(round_trip.get("key") == Some(&1));
+    }
+
+    #[test]
+    fn read_json_file_has_path_context() {
+        let path = Path::new("test_tmp/read_json_file_missing.json");
+        let error = read_json_file::<u32>(path).expect_err("read missing file");
+
+        assert!(error.to_string().contains("read_json_file_missing.json"));
+    }
+}
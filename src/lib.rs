@@ -1,23 +1,171 @@
 #[cfg(feature = "download-to-file")]
 mod download_to_file;
+#[cfg(feature = "jsonschema")]
+pub use self::download_to_file::download_json_validated;
+#[cfg(feature = "download-to-file")]
+pub use self::download_to_file::download_resume;
+#[cfg(feature = "download-to-file")]
+pub use self::download_to_file::download_to_channel;
 #[cfg(feature = "download-to-file")]
 pub use self::download_to_file::download_to_file;
+#[cfg(feature = "download-to-file")]
+pub use self::download_to_file::download_to_file_limited_redirects;
+#[cfg(feature = "download-to-file-pinned")]
+pub use self::download_to_file::download_to_file_pinned;
+#[cfg(feature = "download-to-file")]
+pub use self::download_to_file::download_to_file_typed;
+#[cfg(feature = "download-to-file")]
+pub use self::download_to_file::download_to_file_with_client_builder;
+#[cfg(feature = "download-to-file")]
+pub use self::download_to_file::download_to_file_with_options;
+#[cfg(feature = "download-to-file")]
+pub use self::download_to_file::download_to_file_with_stats;
+#[cfg(feature = "download-to-file")]
+pub use self::download_to_file::download_to_writer;
+#[cfg(feature = "download-to-file")]
+pub use self::download_to_file::DownloadProgress;
+#[cfg(feature = "download-to-file")]
+pub use self::download_to_file::DownloadStats;
+#[cfg(feature = "download-to-file")]
+pub use self::download_to_file::DownloadToFileOptions;
+#[cfg(feature = "jsonschema")]
+pub use self::download_to_file::JsonValidationError;
 
 #[cfg(feature = "drop-remove-path")]
 mod drop_remove_path;
 #[cfg(feature = "drop-remove-path")]
 pub use self::drop_remove_path::DropRemovePath;
+#[cfg(feature = "drop-remove-path")]
+pub use self::drop_remove_path::SharedDropRemovePath;
+
+#[cfg(any(feature = "drop-remove-path", feature = "drop-remove-path-blocking"))]
+mod remove_mode;
+#[cfg(any(feature = "drop-remove-path", feature = "drop-remove-path-blocking"))]
+pub use self::remove_mode::RemoveMode;
+
+#[cfg(feature = "drop-remove-path-blocking")]
+mod drop_remove_path_blocking;
+#[cfg(feature = "drop-remove-path-blocking")]
+pub use self::drop_remove_path_blocking::DropRemovePathBlocking;
+
+#[cfg(feature = "drop-remove-path-blocking")]
+mod drop_remove_path_set_blocking;
+#[cfg(feature = "drop-remove-path-blocking")]
+pub use self::drop_remove_path_set_blocking::DropRemovePathSetBlocking;
+
+#[cfg(feature = "drop-remove-path-blocking")]
+mod persist_on_success;
+#[cfg(feature = "drop-remove-path-blocking")]
+pub use self::persist_on_success::PersistOnSuccess;
+
+#[cfg(feature = "drop-remove-path")]
+mod temp_to_final_path;
+#[cfg(feature = "drop-remove-path")]
+pub use self::temp_to_final_path::TempToFinalPath;
+
+#[cfg(feature = "drop-remove-path-blocking")]
+mod temp_to_final_path_blocking;
+#[cfg(feature = "drop-remove-path-blocking")]
+pub use self::temp_to_final_path_blocking::TempToFinalPathBlocking;
 
 #[cfg(feature = "download-to-path")]
 mod download_to_path;
 #[cfg(feature = "download-to-path")]
+pub use self::download_to_path::download_to_dir;
+#[cfg(feature = "download-to-path")]
 pub use self::download_to_path::download_to_path;
+#[cfg(feature = "download-to-path")]
+pub use self::download_to_path::download_to_path_blocking;
+#[cfg(feature = "download-to-path")]
+pub use self::download_to_path::download_to_path_parallel;
+#[cfg(feature = "download-to-path")]
+pub use self::download_to_path::download_to_path_with_client_builder;
+#[cfg(feature = "download-to-path")]
+pub use self::download_to_path::download_to_path_with_fallback;
+#[cfg(feature = "download-to-path")]
+pub use self::download_to_path::download_to_path_with_options;
+#[cfg(feature = "download-to-path")]
+pub use self::download_to_path::DownloadOutcome;
+#[cfg(feature = "download-to-path")]
+pub use self::download_to_path::DownloadToPathOptions;
+
+#[cfg(feature = "download-to-path-metadata")]
+mod download_metadata;
+#[cfg(feature = "download-to-path-metadata")]
+pub use self::download_metadata::DownloadMetadata;
 
 #[cfg(feature = "arc-anyhow-error")]
 mod arc_anyhow_error;
 #[cfg(feature = "arc-anyhow-error")]
 pub use self::arc_anyhow_error::ArcAnyhowError;
 
+#[cfg(feature = "drop-keep-on-success-blocking")]
+mod drop_keep_on_success_blocking;
+#[cfg(feature = "drop-keep-on-success-blocking")]
+pub use self::drop_keep_on_success_blocking::DropKeepOnSuccessBlocking;
+
+#[cfg(feature = "drop-keep-on-success")]
+mod drop_keep_on_success;
+#[cfg(feature = "drop-keep-on-success")]
+pub use self::drop_keep_on_success::DropKeepOnSuccess;
+
+#[cfg(any(feature = "write-file-atomic", feature = "write-file-atomic-async"))]
+mod write_file_atomic;
+#[cfg(feature = "write-file-atomic")]
+pub use self::write_file_atomic::read_file_with_lock;
+#[cfg(feature = "write-file-atomic")]
+pub use self::write_file_atomic::write_file_atomic;
+#[cfg(feature = "write-file-atomic-async")]
+pub use self::write_file_atomic::write_file_atomic_async;
+#[cfg(feature = "write-file-atomic")]
+pub use self::write_file_atomic::write_file_if_changed;
+#[cfg(feature = "write-file-atomic-async")]
+pub use self::write_file_atomic::write_file_if_changed_async;
+
+#[cfg(feature = "json")]
+mod json;
+#[cfg(feature = "json")]
+pub use self::json::read_json_file;
+#[cfg(feature = "json")]
+pub use self::json::write_json_atomic;
+
+#[cfg(all(feature = "file-lock", any(unix, windows)))]
+mod file_lock;
+#[cfg(all(feature = "file-lock", any(unix, windows)))]
+pub use self::file_lock::FileLock;
+#[cfg(all(feature = "file-lock", any(unix, windows)))]
+pub use self::file_lock::FileLockGuard;
+
+#[cfg(all(feature = "file-lock-async", any(unix, windows)))]
+mod async_file_lock;
+#[cfg(all(feature = "file-lock-async", any(unix, windows)))]
+pub use self::async_file_lock::AsyncFileLock;
+#[cfg(all(feature = "file-lock-async", any(unix, windows)))]
+pub use self::async_file_lock::AsyncFileLockGuard;
+
+#[cfg(feature = "dedup")]
+mod deduplicating_downloader;
+#[cfg(feature = "dedup")]
+pub use self::deduplicating_downloader::DeduplicatingDownloader;
+
+#[cfg(feature = "download-cache")]
+mod download_cache;
+#[cfg(feature = "download-cache")]
+pub use self::download_cache::DownloadCache;
+
+#[cfg(feature = "backoff")]
+mod backoff;
+#[cfg(feature = "backoff")]
+pub use self::backoff::Backoff;
+
+#[cfg(feature = "watch-file")]
+mod watch_file;
+#[cfg(feature = "watch-file")]
+pub use self::watch_file::watch_file_for_deletion;
+
+#[cfg(all(test, feature = "download-to-file"))]
+mod test_server;
+
 use std::ffi::OsStr;
 use std::ffi::OsString;
 use std::path::Path;
@@ -45,7 +193,7 @@ where
     path_string.reserve(extension.len() + 1);
     path_string.push(".");
     path_string.push(extension);
-    std::mem::swap(path, &mut path_string.into());
+    *path = path_string.into();
 }
 
 /// Push an extension to a [`Path`], returning a new [`PathBuf`].
@@ -71,6 +219,15 @@ where
     PathBuf::from(path_string)
 }
 
+/// Add an extension to a [`Path`] only if it does not already have one, returning a new [`PathBuf`].
+pub fn with_extension_if_missing(path: &Path, extension: &str) -> PathBuf {
+    if path.extension().is_some() {
+        return path.into();
+    }
+
+    path.with_extension(extension)
+}
+
 /// Try to create a dir at the given path.
 ///
 /// # Returns
@@ -88,6 +245,59 @@ where
     }
 }
 
+/// Ensure that a dir exists at the given path, creating it and all of its parents if needed.
+///
+/// Unlike checking if the dir exists before creating it, this is not racy.
+/// If the dir is created by someone else while this runs, this still succeeds.
+///
+/// # Returns
+/// Returns `Ok(())` if the dir exists.
+/// Returns an error if the dir could not be created, or the path exists but is not a dir.
+pub fn ensure_dir_exists_or_create<P>(path: P) -> std::io::Result<()>
+where
+    P: AsRef<Path>,
+{
+    let path = path.as_ref();
+    match std::fs::create_dir_all(path) {
+        Ok(()) => Ok(()),
+        Err(error) if error.kind() == std::io::ErrorKind::AlreadyExists && path.is_dir() => Ok(()),
+        Err(error) => Err(error),
+    }
+}
+
+/// Try to create all parent dirs of the given file path.
+///
+/// This is useful before creating a file, as a replacement for `std::fs::create_dir_all(path.parent().unwrap())`.
+/// A path without a parent, like "file.txt" or "/", has nothing to create.
+///
+/// # Returns
+/// Returns `Ok(true)` if any dirs were created.
+/// Returns `Ok(false)` if the parent dir already exists.
+/// Returns an error if the dirs could not be created, or the parent path exists but is not a dir.
+pub fn try_create_parent_dirs<P>(path: P) -> std::io::Result<bool>
+where
+    P: AsRef<Path>,
+{
+    let parent = match path.as_ref().parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => return Ok(false),
+    };
+
+    match try_create_dir(parent) {
+        Ok(true) => Ok(true),
+        Ok(false) if parent.is_dir() => Ok(false),
+        Ok(false) => Err(std::io::Error::new(
+            std::io::ErrorKind::AlreadyExists,
+            "the parent path exists but is not a dir",
+        )),
+        Err(error) if error.kind() == std::io::ErrorKind::NotFound => {
+            ensure_dir_exists_or_create(parent)?;
+            Ok(true)
+        }
+        Err(error) => Err(error),
+    }
+}
+
 /// Try to remove a dir at the given path.
 ///
 /// # Returns
@@ -105,6 +315,384 @@ where
     }
 }
 
+/// Try to remove a dir and all of its contents at the given path.
+///
+/// # Returns
+/// Returns `Ok(true)` if the dir was removed.
+/// Returns `Ok(false)` if the dir did not exist.
+/// Returns and error if there was an error removing the dir.
+pub fn try_remove_dir_all<P>(path: P) -> std::io::Result<bool>
+where
+    P: AsRef<Path>,
+{
+    match std::fs::remove_dir_all(path) {
+        Ok(()) => Ok(true),
+        Err(error) if error.kind() == std::io::ErrorKind::NotFound => Ok(false),
+        Err(error) => Err(error),
+    }
+}
+
+/// Ensure that no file exists at the given path, removing it if needed.
+///
+/// # Returns
+/// Returns `Ok(())` if the file was removed or did not exist.
+/// Returns an error if there was an error removing the file.
+pub fn ensure_file_absent<P>(path: P) -> std::io::Result<()>
+where
+    P: AsRef<Path>,
+{
+    match std::fs::remove_file(path) {
+        Ok(()) => Ok(()),
+        Err(error) if error.kind() == std::io::ErrorKind::NotFound => Ok(()),
+        Err(error) => Err(error),
+    }
+}
+
+/// Ensure that no dir exists at the given path, removing it and all of its contents if needed.
+///
+/// # Returns
+/// Returns `Ok(())` if the dir was removed or did not exist.
+/// Returns an error if there was an error removing the dir.
+pub fn ensure_dir_absent<P>(path: P) -> std::io::Result<()>
+where
+    P: AsRef<Path>,
+{
+    match std::fs::remove_dir_all(path) {
+        Ok(()) => Ok(()),
+        Err(error) if error.kind() == std::io::ErrorKind::NotFound => Ok(()),
+        Err(error) => Err(error),
+    }
+}
+
+/// Try to read a file at the given path.
+///
+/// # Returns
+/// Returns `Ok(Some(bytes))` if the file was read.
+/// Returns `Ok(None)` if the file did not exist.
+/// Returns an error if there was an error reading the file.
+pub fn try_read_file<P>(path: P) -> std::io::Result<Option<Vec<u8>>>
+where
+    P: AsRef<Path>,
+{
+    match std::fs::read(path) {
+        Ok(bytes) => Ok(Some(bytes)),
+        Err(error) if error.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(error) => Err(error),
+    }
+}
+
+/// Try to read a file at the given path to a string.
+///
+/// # Returns
+/// Returns `Ok(Some(string))` if the file was read.
+/// Returns `Ok(None)` if the file did not exist.
+/// Returns an error if there was an error reading the file, or the file is not valid UTF-8.
+pub fn try_read_to_string<P>(path: P) -> std::io::Result<Option<String>>
+where
+    P: AsRef<Path>,
+{
+    match std::fs::read_to_string(path) {
+        Ok(string) => Ok(Some(string)),
+        Err(error) if error.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(error) => Err(error),
+    }
+}
+
+/// Check whether a file exists at the given path and is not empty.
+///
+/// This check is best-effort, as the file can be created, changed, or removed right after it is checked.
+/// Don't rely on the file still being in the same state when the result is used.
+///
+/// # Returns
+/// Returns `Ok(true)` if the file exists and has a length greater than 0.
+/// Returns `Ok(false)` if the file did not exist or is empty.
+/// Returns an error if there was an error getting the metadata of the file.
+pub fn file_exists_and_is_nonempty<P>(path: P) -> std::io::Result<bool>
+where
+    P: AsRef<Path>,
+{
+    match std::fs::metadata(path) {
+        Ok(metadata) => Ok(metadata.len() > 0),
+        Err(error) if error.kind() == std::io::ErrorKind::NotFound => Ok(false),
+        Err(error) => Err(error),
+    }
+}
+
+/// Check whether a file exists at the given path and is not empty, asynchronously.
+///
+/// This is the async version of [`file_exists_and_is_nonempty`].
+/// This check is best-effort, as the file can be created, changed, or removed right after it is checked.
+/// Don't rely on the file still being in the same state when the result is used.
+///
+/// # Returns
+/// Returns `Ok(true)` if the file exists and has a length greater than 0.
+/// Returns `Ok(false)` if the file did not exist or is empty.
+/// Returns an error if there was an error getting the metadata of the file.
+#[cfg(feature = "fs-async")]
+pub async fn file_exists_and_is_nonempty_async<P>(path: P) -> std::io::Result<bool>
+where
+    P: AsRef<Path>,
+{
+    match tokio::fs::metadata(path).await {
+        Ok(metadata) => Ok(metadata.len() > 0),
+        Err(error) if error.kind() == std::io::ErrorKind::NotFound => Ok(false),
+        Err(error) => Err(error),
+    }
+}
+
+/// Try to copy a file from one path to another.
+///
+/// # Returns
+/// Returns `Ok(Some(bytes_copied))` if the file was copied.
+/// Returns `Ok(None)` if the source file did not exist.
+/// Returns and error if there was an error copying the file,
+/// including if the parent directory of the destination did not exist.
+pub fn try_copy_file<P, Q>(from: P, to: Q) -> std::io::Result<Option<u64>>
+where
+    P: AsRef<Path>,
+    Q: AsRef<Path>,
+{
+    let from = from.as_ref();
+    match std::fs::copy(from, to) {
+        Ok(bytes_copied) => Ok(Some(bytes_copied)),
+        Err(error) if error.kind() == std::io::ErrorKind::NotFound => {
+            ensure_source_missing(from, error)?;
+            Ok(None)
+        }
+        Err(error) => Err(error),
+    }
+}
+
+/// Check that a `NotFound` error from an operation was caused by its source not existing.
+///
+/// The OS also reports a missing parent directory of the destination as `NotFound`,
+/// so this checks the source itself.
+///
+/// # Returns
+/// Returns `Ok(())` if the source does not exist.
+/// Returns the given error otherwise.
+fn ensure_source_missing(source: &Path, error: std::io::Error) -> std::io::Result<()> {
+    match std::fs::symlink_metadata(source) {
+        Err(source_error) if source_error.kind() == std::io::ErrorKind::NotFound => Ok(()),
+        _ => Err(error),
+    }
+}
+
+/// The extension added to the destination to get the temporary file used when moving across filesystems.
+const MOVE_TEMP_EXTENSION: &str = "tmp";
+
+/// Try to move a file from one path to another.
+///
+/// This renames the file if possible.
+/// If the paths are on different filesystems, where a rename is not possible,
+/// the file is copied to a temporary file next to the destination, like "file.txt.tmp",
+/// which is renamed over the destination before the source is removed.
+/// If copying fails, only the temporary file is removed, so an existing destination is kept.
+/// The temporary file must not already exist.
+///
+/// # Returns
+/// Returns `Ok(true)` if the file was moved.
+/// Returns `Ok(false)` if the source file did not exist.
+/// Returns an error if there was an error moving the file,
+/// including if the parent directory of the destination did not exist.
+pub fn try_move_file(from: &Path, to: &Path) -> std::io::Result<bool> {
+    match std::fs::rename(from, to) {
+        Ok(()) => return Ok(true),
+        Err(error) if error.kind() == std::io::ErrorKind::NotFound => {
+            ensure_source_missing(from, error)?;
+            return Ok(false);
+        }
+        Err(error) if error.kind() == std::io::ErrorKind::CrossesDevices => {}
+        Err(error) => return Err(error),
+    }
+
+    move_file_by_copy(from, to)?;
+
+    Ok(true)
+}
+
+/// Move a file by copying it through a temporary file next to the destination.
+///
+/// This is the fallback of [`try_move_file`] for paths on different filesystems.
+fn move_file_by_copy(from: &Path, to: &Path) -> std::io::Result<()> {
+    let temp_path = with_push_extension(to, MOVE_TEMP_EXTENSION);
+
+    let mut from_file = std::fs::File::open(from)?;
+    let permissions = from_file.metadata()?.permissions();
+    let mut temp_file = std::fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(&temp_path)?;
+
+    // The temporary file was created here, so it is the only file that is safe to remove.
+    let result = (|| {
+        std::io::copy(&mut from_file, &mut temp_file)?;
+        temp_file.set_permissions(permissions)?;
+        temp_file.sync_all()?;
+        drop(temp_file);
+        std::fs::rename(&temp_path, to)
+    })();
+    if let Err(error) = result {
+        let _ = std::fs::remove_file(&temp_path);
+        return Err(error);
+    }
+    std::fs::remove_file(from)
+}
+
+/// Try to move a file from one path to another, asynchronously.
+///
+/// This is the async version of [`try_move_file`].
+/// If the paths are on different filesystems, the file is copied with [`tokio::io::copy`],
+/// along with its permissions, through a temporary file next to the destination,
+/// which is renamed over the destination before the source is removed.
+/// If copying fails, only the temporary file is removed, so an existing destination is kept.
+/// The temporary file must not already exist.
+///
+/// # Returns
+/// Returns `Ok(true)` if the file was moved.
+/// Returns `Ok(false)` if the source file did not exist.
+/// Returns an error if there was an error moving the file,
+/// including if the parent directory of the destination did not exist.
+#[cfg(feature = "fs-async")]
+pub async fn try_move_file_async(from: &Path, to: &Path) -> std::io::Result<bool> {
+    match tokio::fs::rename(from, to).await {
+        Ok(()) => return Ok(true),
+        Err(error) if error.kind() == std::io::ErrorKind::NotFound => {
+            match tokio::fs::symlink_metadata(from).await {
+                Err(from_error) if from_error.kind() == std::io::ErrorKind::NotFound => {
+                    return Ok(false);
+                }
+                _ => return Err(error),
+            }
+        }
+        Err(error) if error.kind() == std::io::ErrorKind::CrossesDevices => {}
+        Err(error) => return Err(error),
+    }
+
+    move_file_by_copy_async(from, to).await?;
+
+    Ok(true)
+}
+
+/// Move a file by copying it through a temporary file next to the destination, asynchronously.
+///
+/// This is the fallback of [`try_move_file_async`] for paths on different filesystems.
+#[cfg(feature = "fs-async")]
+async fn move_file_by_copy_async(from: &Path, to: &Path) -> std::io::Result<()> {
+    let temp_path = with_push_extension(to, MOVE_TEMP_EXTENSION);
+
+    let mut from_file = tokio::fs::File::open(from).await?;
+    let permissions = from_file.metadata().await?.permissions();
+    let mut temp_file = tokio::fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(&temp_path)
+        .await?;
+
+    // The temporary file was created here, so it is the only file that is safe to remove.
+    let result = async {
+        tokio::io::copy(&mut from_file, &mut temp_file).await?;
+        temp_file.set_permissions(permissions).await?;
+        temp_file.sync_all().await?;
+        drop(temp_file);
+        tokio::fs::rename(&temp_path, to).await
+    }
+    .await;
+    if let Err(error) = result {
+        let _ = tokio::fs::remove_file(&temp_path).await;
+        return Err(error);
+    }
+    tokio::fs::remove_file(from).await
+}
+
+/// Try to create a hard link to a file.
+///
+/// # Returns
+/// Returns `Ok(true)` if the link was created.
+/// Returns `Ok(false)` if the original file did not exist.
+/// Returns and error if there was an error creating the link,
+/// including if the parent directory of the link did not exist.
+pub fn try_hard_link<P, Q>(original: P, link: Q) -> std::io::Result<bool>
+where
+    P: AsRef<Path>,
+    Q: AsRef<Path>,
+{
+    let original = original.as_ref();
+    match std::fs::hard_link(original, link) {
+        Ok(()) => Ok(true),
+        Err(error) if error.kind() == std::io::ErrorKind::NotFound => {
+            ensure_source_missing(original, error)?;
+            Ok(false)
+        }
+        Err(error) => Err(error),
+    }
+}
+
+/// Try to create a symbolic link to a file.
+///
+/// This uses [`std::os::unix::fs::symlink`] on Unix and [`std::os::windows::fs::symlink_file`] on Windows.
+///
+/// # Returns
+/// Returns `Ok(true)` if the link was created.
+/// Returns `Ok(false)` if the original file did not exist.
+/// Returns and error if there was an error creating the link.
+///
+/// Since the OS allows links to missing files, the original is checked before the link is created.
+/// This check is relative to the current directory, not the directory of the link,
+/// so a relative `original` should only be used when both are the same.
+#[cfg(any(unix, windows))]
+pub fn try_symlink_file(original: &Path, link: &Path) -> std::io::Result<bool> {
+    if !original.try_exists()? {
+        return Ok(false);
+    }
+
+    #[cfg(unix)]
+    std::os::unix::fs::symlink(original, link)?;
+
+    #[cfg(windows)]
+    std::os::windows::fs::symlink_file(original, link)?;
+
+    Ok(true)
+}
+
+/// Atomically replace the file at `dst` with the file at `src`.
+///
+/// On Unix, this is a `rename`, which replaces the destination atomically.
+/// On Windows, [`std::fs::rename`] already uses `MoveFileExW` with `MOVEFILE_REPLACE_EXISTING`,
+/// but it fails while another process has the destination open without sharing it for deletion,
+/// like an antivirus scanner or an indexer.
+/// Since these usually hold the file briefly, this retries on Windows a few times, waiting between attempts.
+///
+/// Both paths must be on the same filesystem.
+///
+/// # Returns
+/// Returns an error if the file could not be renamed,
+/// which on Windows is the error of the last attempt.
+pub fn atomic_replace_file(src: &Path, dst: &Path) -> std::io::Result<()> {
+    #[cfg(windows)]
+    {
+        // ERROR_ACCESS_DENIED and ERROR_SHARING_VIOLATION
+        const LOCKED_ERRORS: [i32; 2] = [5, 32];
+        const RETRIES: u32 = 5;
+        const RETRY_DELAY: std::time::Duration = std::time::Duration::from_millis(50);
+
+        for _ in 0..RETRIES {
+            match std::fs::rename(src, dst) {
+                Err(error)
+                    if error
+                        .raw_os_error()
+                        .is_some_and(|code| LOCKED_ERRORS.contains(&code)) =>
+                {
+                    std::thread::sleep(RETRY_DELAY);
+                }
+                result => return result,
+            }
+        }
+    }
+
+    std::fs::rename(src, dst)
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -132,6 +720,15 @@ mod test {
         assert!(push_extension_path == expected);
     }
 
+    #[test]
+    fn with_extension_if_missing_works() {
+        let path = with_extension_if_missing(Path::new("file"), "txt");
+        assert!(path == Path::new("file.txt"));
+
+        let path = with_extension_if_missing(Path::new("file.json"), "txt");
+        assert!(path == Path::new("file.json"));
+    }
+
     #[test]
     fn try_create_dir_works() {
         let path = "test_tmp/try_create_dir";
@@ -144,4 +741,365 @@ mod test {
         assert!(try_remove_dir(path).expect("failed to remove dir"));
         assert!(!try_remove_dir(path).expect("failed to remove dir"));
     }
+
+    #[test]
+    fn ensure_dir_exists_or_create_works() {
+        let path = "test_tmp/ensure_dir_exists_or_create/nested";
+
+        let _ = std::fs::remove_dir_all("test_tmp/ensure_dir_exists_or_create");
+        ensure_dir_exists_or_create(path).expect("failed to create dir");
+        ensure_dir_exists_or_create(path).expect("failed to create dir");
+        assert!(Path::new(path).is_dir());
+
+        let file_path = "test_tmp/ensure_dir_exists_or_create/file";
+        std::fs::write(file_path, "data").expect("failed to write file");
+        ensure_dir_exists_or_create(file_path).expect_err("created dir over file");
+    }
+
+    #[test]
+    fn try_create_parent_dirs_works() {
+        let path = "test_tmp/try_create_parent_dirs/nested/file.txt";
+
+        let _ = std::fs::remove_dir_all("test_tmp/try_create_parent_dirs");
+        assert!(try_create_parent_dirs(path).expect("failed to create dirs"));
+        assert!(!try_create_parent_dirs(path).expect("failed to create dirs"));
+        assert!(Path::new("test_tmp/try_create_parent_dirs/nested").is_dir());
+        assert!(!try_create_parent_dirs("file.txt").expect("failed to create dirs"));
+
+        let file_path = "test_tmp/try_create_parent_dirs/file";
+        std::fs::write(file_path, "data").expect("failed to write file");
+        try_create_parent_dirs(format!("{file_path}/file.txt")).expect_err("created dir over file");
+    }
+
+    #[test]
+    fn try_remove_dir_all_works() {
+        let path = "test_tmp/try_remove_dir_all";
+
+        std::fs::create_dir_all(format!("{path}/nested")).expect("failed to create dir");
+        std::fs::write(format!("{path}/nested/file.txt"), "data").expect("failed to write file");
+
+        assert!(try_remove_dir_all(path).expect("failed to remove dir"));
+        assert!(!try_remove_dir_all(path).expect("failed to remove dir"));
+    }
+
+    #[test]
+    fn ensure_file_absent_works() {
+        let path = "test_tmp/ensure_file_absent.txt";
+
+        std::fs::create_dir_all("test_tmp").expect("failed to create tmp dir");
+        std::fs::write(path, "data").expect("failed to write file");
+
+        ensure_file_absent(path).expect("failed to remove file");
+        assert!(!Path::new(path).exists());
+        ensure_file_absent(path).expect("failed to remove file");
+    }
+
+    #[test]
+    fn ensure_dir_absent_works() {
+        let path = "test_tmp/ensure_dir_absent";
+
+        std::fs::create_dir_all(format!("{path}/nested")).expect("failed to create dir");
+        std::fs::write(format!("{path}/nested/file.txt"), "data").expect("failed to write file");
+
+        ensure_dir_absent(path).expect("failed to remove dir");
+        assert!(!Path::new(path).exists());
+        ensure_dir_absent(path).expect("failed to remove dir");
+    }
+
+    #[test]
+    fn try_copy_file_works() {
+        let from = "test_tmp/try_copy_file_from.txt";
+        let to = "test_tmp/try_copy_file_to.txt";
+
+        std::fs::create_dir_all("test_tmp").expect("failed to create tmp dir");
+
+        let _ = std::fs::remove_file(from);
+        assert!(try_copy_file(from, to)
+            .expect("failed to copy file")
+            .is_none());
+
+        std::fs::write(from, "data").expect("failed to write file");
+        assert!(try_copy_file(from, to).expect("failed to copy file") == Some(4));
+        assert!(std::fs::read(to).expect("failed to read file") == b"data");
+
+        try_copy_file(from, "test_tmp/try_copy_file_missing/to.txt")
+            .expect_err("a missing destination directory was treated as a missing source");
+    }
+
+    #[test]
+    fn try_read_file_works() {
+        let path = "test_tmp/try_read_file.txt";
+
+        std::fs::create_dir_all("test_tmp").expect("failed to create tmp dir");
+
+        let _ = std::fs::remove_file(path);
+        assert!(try_read_file(path).expect("failed to read file").is_none());
+
+        std::fs::write(path, "data").expect("failed to write file");
+        assert!(try_read_file(path).expect("failed to read file") == Some(b"data".to_vec()));
+    }
+
+    #[test]
+    fn try_read_to_string_works() {
+        let path = "test_tmp/try_read_to_string.txt";
+
+        std::fs::create_dir_all("test_tmp").expect("failed to create tmp dir");
+
+        let _ = std::fs::remove_file(path);
+        assert!(try_read_to_string(path)
+            .expect("failed to read file")
+            .is_none());
+
+        std::fs::write(path, "data").expect("failed to write file");
+        assert!(try_read_to_string(path).expect("failed to read file") == Some("data".into()));
+
+        std::fs::write(path, b"\xff").expect("failed to write file");
+        try_read_to_string(path).expect_err("read invalid UTF-8");
+    }
+
+    #[test]
+    fn file_exists_and_is_nonempty_works() {
+        let path = "test_tmp/file_exists_and_is_nonempty.txt";
+
+        std::fs::create_dir_all("test_tmp").expect("failed to create tmp dir");
+
+        let _ = std::fs::remove_file(path);
+        assert!(!file_exists_and_is_nonempty(path).expect("failed to check file"));
+
+        std::fs::write(path, "").expect("failed to write file");
+        assert!(!file_exists_and_is_nonempty(path).expect("failed to check file"));
+
+        std::fs::write(path, "data").expect("failed to write file");
+        assert!(file_exists_and_is_nonempty(path).expect("failed to check file"));
+    }
+
+    #[cfg(feature = "fs-async")]
+    #[tokio::test]
+    async fn file_exists_and_is_nonempty_async_works() {
+        let path = "test_tmp/file_exists_and_is_nonempty_async.txt";
+
+        tokio::fs::create_dir_all("test_tmp")
+            .await
+            .expect("failed to create tmp dir");
+
+        let _ = tokio::fs::remove_file(path).await;
+        assert!(!file_exists_and_is_nonempty_async(path)
+            .await
+            .expect("failed to check file"));
+
+        tokio::fs::write(path, "")
+            .await
+            .expect("failed to write file");
+        assert!(!file_exists_and_is_nonempty_async(path)
+            .await
+            .expect("failed to check file"));
+
+        tokio::fs::write(path, "data")
+            .await
+            .expect("failed to write file");
+        assert!(file_exists_and_is_nonempty_async(path)
+            .await
+            .expect("failed to check file"));
+    }
+
+    #[test]
+    fn atomic_replace_file_works() {
+        let src = Path::new("test_tmp/atomic_replace_file.txt.tmp");
+        let dst = Path::new("test_tmp/atomic_replace_file.txt");
+
+        std::fs::create_dir_all("test_tmp").expect("failed to create tmp dir");
+        std::fs::write(dst, "old").expect("failed to write file");
+        std::fs::write(src, "new").expect("failed to write file");
+
+        atomic_replace_file(src, dst).expect("failed to replace file");
+        assert!(!src.exists());
+        assert!(std::fs::read(dst).expect("failed to read file") == b"new");
+
+        atomic_replace_file(src, dst).expect_err("replaced with a missing file");
+    }
+
+    #[test]
+    fn try_move_file_works() {
+        let from = Path::new("test_tmp/try_move_file_from.txt");
+        let to = Path::new("test_tmp/try_move_file_to.txt");
+
+        std::fs::create_dir_all("test_tmp").expect("failed to create tmp dir");
+        let _ = std::fs::remove_file(from);
+
+        assert!(!try_move_file(from, to).expect("failed to move file"));
+
+        std::fs::write(from, "data").expect("failed to write file");
+        assert!(try_move_file(from, to).expect("failed to move file"));
+        assert!(!from.exists());
+        assert!(std::fs::read(to).expect("failed to read file") == b"data");
+
+        std::fs::write(from, "data").expect("failed to write file");
+        try_move_file(from, Path::new("test_tmp/try_move_file_missing/to.txt"))
+            .expect_err("a missing destination directory was treated as a missing source");
+        assert!(from.exists());
+    }
+
+    #[test]
+    fn move_file_by_copy_works() {
+        let from = Path::new("test_tmp/move_file_by_copy_from.txt");
+        let to = Path::new("test_tmp/move_file_by_copy_to.txt");
+        let temp = Path::new("test_tmp/move_file_by_copy_to.txt.tmp");
+
+        std::fs::create_dir_all("test_tmp").expect("failed to create tmp dir");
+        let _ = std::fs::remove_file(temp);
+        std::fs::write(from, "new").expect("failed to write file");
+        std::fs::write(to, "old").expect("failed to write file");
+
+        move_file_by_copy(from, to).expect("failed to move file");
+        assert!(!from.exists());
+        assert!(!temp.exists());
+        assert!(std::fs::read(to).expect("failed to read file") == b"new");
+    }
+
+    #[test]
+    fn move_file_by_copy_keeps_destination_on_failure() {
+        let from = Path::new("test_tmp/move_file_by_copy_failure_from");
+        let to = Path::new("test_tmp/move_file_by_copy_failure_to.txt");
+        let temp = Path::new("test_tmp/move_file_by_copy_failure_to.txt.tmp");
+
+        // A directory can't be copied as a file.
+        std::fs::create_dir_all(from).expect("failed to create dir");
+        let _ = std::fs::remove_file(temp);
+        std::fs::write(to, "old").expect("failed to write file");
+
+        move_file_by_copy(from, to).expect_err("copied a directory");
+        assert!(from.exists());
+        assert!(!temp.exists());
+        assert!(std::fs::read(to).expect("failed to read file") == b"old");
+    }
+
+    #[cfg(feature = "fs-async")]
+    #[tokio::test]
+    async fn try_move_file_async_works() {
+        let from = Path::new("test_tmp/try_move_file_async_from.txt");
+        let to = Path::new("test_tmp/try_move_file_async_to.txt");
+
+        tokio::fs::create_dir_all("test_tmp")
+            .await
+            .expect("failed to create tmp dir");
+        let _ = tokio::fs::remove_file(from).await;
+
+        assert!(!try_move_file_async(from, to)
+            .await
+            .expect("failed to move file"));
+
+        tokio::fs::write(from, "data")
+            .await
+            .expect("failed to write file");
+        assert!(try_move_file_async(from, to)
+            .await
+            .expect("failed to move file"));
+        assert!(!from.exists());
+        assert!(tokio::fs::read(to).await.expect("failed to read file") == b"data");
+
+        tokio::fs::write(from, "data")
+            .await
+            .expect("failed to write file");
+        try_move_file_async(
+            from,
+            Path::new("test_tmp/try_move_file_async_missing/to.txt"),
+        )
+        .await
+        .expect_err("a missing destination directory was treated as a missing source");
+        assert!(from.exists());
+    }
+
+    #[cfg(feature = "fs-async")]
+    #[tokio::test]
+    async fn move_file_by_copy_async_works() {
+        let from = Path::new("test_tmp/move_file_by_copy_async_from.txt");
+        let to = Path::new("test_tmp/move_file_by_copy_async_to.txt");
+        let temp = Path::new("test_tmp/move_file_by_copy_async_to.txt.tmp");
+
+        tokio::fs::create_dir_all("test_tmp")
+            .await
+            .expect("failed to create tmp dir");
+        let _ = tokio::fs::remove_file(temp).await;
+        tokio::fs::write(from, "new")
+            .await
+            .expect("failed to write file");
+        tokio::fs::write(to, "old")
+            .await
+            .expect("failed to write file");
+
+        move_file_by_copy_async(from, to)
+            .await
+            .expect("failed to move file");
+        assert!(!from.exists());
+        assert!(!temp.exists());
+        assert!(tokio::fs::read(to).await.expect("failed to read file") == b"new");
+    }
+
+    #[cfg(feature = "fs-async")]
+    #[tokio::test]
+    async fn move_file_by_copy_async_keeps_destination_on_failure() {
+        let from = Path::new("test_tmp/move_file_by_copy_async_failure_from");
+        let to = Path::new("test_tmp/move_file_by_copy_async_failure_to.txt");
+        let temp = Path::new("test_tmp/move_file_by_copy_async_failure_to.txt.tmp");
+
+        // A directory can't be copied as a file.
+        tokio::fs::create_dir_all(from)
+            .await
+            .expect("failed to create dir");
+        let _ = tokio::fs::remove_file(temp).await;
+        tokio::fs::write(to, "old")
+            .await
+            .expect("failed to write file");
+
+        move_file_by_copy_async(from, to)
+            .await
+            .expect_err("copied a directory");
+        assert!(from.exists());
+        assert!(!temp.exists());
+        assert!(tokio::fs::read(to).await.expect("failed to read file") == b"old");
+
+        // A missing source is also reported before anything is created.
+        move_file_by_copy_async(Path::new("test_tmp/move_file_by_copy_async_missing"), to)
+            .await
+            .expect_err("moved a missing file");
+        assert!(!temp.exists());
+        assert!(tokio::fs::read(to).await.expect("failed to read file") == b"old");
+    }
+
+    #[test]
+    fn try_hard_link_works() {
+        let original = "test_tmp/try_hard_link_original.txt";
+        let link = "test_tmp/try_hard_link_link.txt";
+
+        std::fs::create_dir_all("test_tmp").expect("failed to create tmp dir");
+        let _ = std::fs::remove_file(original);
+        let _ = std::fs::remove_file(link);
+
+        assert!(!try_hard_link(original, link).expect("failed to link file"));
+
+        std::fs::write(original, "data").expect("failed to write file");
+        assert!(try_hard_link(original, link).expect("failed to link file"));
+        assert!(std::fs::read(link).expect("failed to read file") == b"data");
+
+        try_hard_link(original, "test_tmp/try_hard_link_missing/link.txt")
+            .expect_err("a missing link directory was treated as a missing original");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn try_symlink_file_works() {
+        let original = Path::new("test_tmp/try_symlink_file_original.txt");
+        let link = Path::new("test_tmp/try_symlink_file_link.txt");
+
+        std::fs::create_dir_all("test_tmp").expect("failed to create tmp dir");
+        let _ = std::fs::remove_file(original);
+        let _ = std::fs::remove_file(link);
+
+        assert!(!try_symlink_file(original, link).expect("failed to link file"));
+
+        std::fs::write(original, "data").expect("failed to write file");
+        let absolute_original = original.canonicalize().expect("failed to canonicalize");
+        assert!(try_symlink_file(&absolute_original, link).expect("failed to link file"));
+        assert!(std::fs::read(link).expect("failed to read file") == b"data");
+    }
 }
@@ -0,0 +1,103 @@
+use crate::DropRemovePathBlocking;
+use std::ops::Deref;
+use std::path::Path;
+
+/// A [`DropRemovePathBlocking`] that is persisted if an operation succeeds.
+///
+/// This replaces the pattern of calling [`DropRemovePathBlocking::persist`] when a result is `Ok`.
+/// The result is given through [`PersistOnSuccess::set_result`] or [`PersistOnSuccess::finish`].
+/// On drop, the guard is persisted if the result is `Ok`,
+/// and otherwise it is dropped as normal, removing the path.
+/// If no result was given, the path is removed.
+#[derive(Debug)]
+pub struct PersistOnSuccess<T, E> {
+    /// The guard
+    guard: DropRemovePathBlocking,
+
+    /// The result of the operation, if it is done.
+    result: Option<Result<T, E>>,
+}
+
+impl<T, E> PersistOnSuccess<T, E> {
+    /// Make a new [`PersistOnSuccess`] for a guard.
+    pub fn new(guard: DropRemovePathBlocking) -> Self {
+        Self {
+            guard,
+            result: None,
+        }
+    }
+
+    /// Set the result of the operation, deciding whether the path is persisted on drop.
+    ///
+    /// This replaces any result that was set before.
+    pub fn set_result(&mut self, result: Result<T, E>) {
+        self.result = Some(result);
+    }
+
+    /// Finish with the result of the operation, returning it.
+    ///
+    /// The guard is persisted if the result is `Ok`, and removes the path otherwise.
+    pub fn finish(mut self, result: Result<T, E>) -> Result<T, E> {
+        if result.is_ok() {
+            self.guard.persist();
+        }
+
+        result
+    }
+}
+
+impl<T, E> AsRef<Path> for PersistOnSuccess<T, E> {
+    fn as_ref(&self) -> &Path {
+        self.guard.as_ref()
+    }
+}
+
+impl<T, E> Deref for PersistOnSuccess<T, E> {
+    type Target = Path;
+
+    fn deref(&self) -> &Self::Target {
+        &self.guard
+    }
+}
+
+impl<T, E> Drop for PersistOnSuccess<T, E> {
+    fn drop(&mut self) {
+        // The guard is dropped after this, removing the path unless it was persisted.
+        if matches!(self.result, Some(Ok(_))) {
+            self.guard.persist();
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn persist_on_success_sanity_check() {
+        std::fs::create_dir_all("test_tmp").expect("failed to create tmp dir");
+
+        let file_path: &Path = "test_tmp/persist_on_success.txt".as_ref();
+
+        std::fs::write(file_path, "data").expect("failed to write file");
+        let guard = PersistOnSuccess::new(DropRemovePathBlocking::new(file_path));
+        assert!(guard.finish(Ok::<_, ()>(1)) == Ok(1));
+        assert!(file_path.exists(), "successful file does not exist");
+
+        let guard = PersistOnSuccess::new(DropRemovePathBlocking::new(file_path));
+        assert!(guard.finish(Err::<(), _>("error")) == Err("error"));
+        assert!(!file_path.exists(), "failed file exists");
+
+        std::fs::write(file_path, "data").expect("failed to write file");
+        {
+            let mut guard = PersistOnSuccess::new(DropRemovePathBlocking::new(file_path));
+            guard.set_result(Ok::<_, ()>(()));
+        }
+        assert!(file_path.exists(), "successful file does not exist");
+
+        drop(PersistOnSuccess::<(), ()>::new(
+            DropRemovePathBlocking::new(file_path),
+        ));
+        assert!(!file_path.exists(), "unfinished file exists");
+    }
+}
@@ -0,0 +1,87 @@
+use std::path::Path;
+
+/// How a path should be removed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RemoveMode {
+    /// The path is a file.
+    File,
+
+    /// The path is a directory, which is removed along with its contents.
+    Dir,
+}
+
+impl RemoveMode {
+    /// Remove the path, blocking the current thread.
+    #[cfg(feature = "drop-remove-path-blocking")]
+    pub(crate) fn remove(self, path: &Path) -> std::io::Result<()> {
+        match self {
+            Self::File => std::fs::remove_file(path),
+            Self::Dir => std::fs::remove_dir_all(path),
+        }
+    }
+
+    /// Remove the path asynchronously.
+    #[cfg(feature = "drop-remove-path")]
+    pub(crate) async fn remove_async(self, path: &Path) -> std::io::Result<()> {
+        match self {
+            Self::File => tokio::fs::remove_file(path).await,
+            Self::Dir => tokio::fs::remove_dir_all(path).await,
+        }
+    }
+}
+
+/// A function that cleans up a path, instead of removing it.
+///
+/// If it fails, it is kept so that it can be called again, like when the guard returned by `try_drop` is dropped.
+pub(crate) type CleanupFn = dyn FnMut(&Path) -> std::io::Result<()> + Send + Sync;
+
+/// The parts of a drop remove path guard, for converting between the async and blocking versions.
+#[cfg(all(feature = "drop-remove-path", feature = "drop-remove-path-blocking"))]
+pub(crate) struct GuardParts {
+    /// The path
+    pub path: std::path::PathBuf,
+
+    /// Whether dropping the guard should remove the path.
+    pub should_remove: bool,
+
+    /// How the path should be removed.
+    pub mode: RemoveMode,
+
+    /// A prefix that the file name must start with for the path to be removed.
+    pub required_prefix: Option<String>,
+
+    /// The function to call instead of removing the path.
+    pub cleanup: Option<Box<CleanupFn>>,
+}
+
+/// Check that the file name of a path starts with the required prefix, if there is one.
+///
+/// # Panics
+/// Panics if the file name does not start with the prefix, unless the thread is already panicking.
+/// In that case, the error is printed instead.
+///
+/// # Return
+/// Returns `false` if the path should not be removed.
+pub(crate) fn check_file_name_prefix(path: &Path, prefix: Option<&str>) -> bool {
+    let Some(prefix) = prefix else {
+        return true;
+    };
+
+    let matches = path
+        .file_name()
+        .and_then(|file_name| file_name.to_str())
+        .is_some_and(|file_name| file_name.starts_with(prefix));
+    if !matches {
+        let message = format!(
+            "refusing to delete path '{}', as its file name does not start with '{prefix}'",
+            path.display()
+        );
+        if std::thread::panicking() {
+            eprintln!("{message}");
+        } else {
+            panic!("{message}");
+        }
+    }
+
+    matches
+}
@@ -0,0 +1,110 @@
+use crate::DropRemovePath;
+use std::ops::Deref;
+use std::path::Path;
+use std::path::PathBuf;
+
+/// A temporary path that is asyncronously renamed to a final path on success.
+///
+/// This is the common pattern of writing to a temporary file, then moving it into place once it is complete.
+/// Call [`TempToFinalPath::commit`] to rename the temporary file to the final path,
+/// or [`TempToFinalPath::abort`] to remove it.
+/// If neither is called, the temporary file is removed on drop, like a [`DropRemovePath`].
+#[derive(Debug)]
+pub struct TempToFinalPath {
+    /// The temporary path
+    temp_path: DropRemovePath,
+
+    /// The final path
+    final_path: PathBuf,
+}
+
+impl TempToFinalPath {
+    /// Make a new [`TempToFinalPath`].
+    pub fn new<P, Q>(temp_path: P, final_path: Q) -> Self
+    where
+        P: AsRef<Path>,
+        Q: AsRef<Path>,
+    {
+        Self {
+            temp_path: DropRemovePath::new(temp_path),
+            final_path: final_path.as_ref().into(),
+        }
+    }
+
+    /// Get the temporary path.
+    pub fn temp_path(&self) -> &Path {
+        &self.temp_path
+    }
+
+    /// Get the final path.
+    pub fn final_path(&self) -> &Path {
+        &self.final_path
+    }
+
+    /// Rename the temporary file to the final path.
+    ///
+    /// If the rename fails, the temporary file is removed in the background.
+    pub async fn commit(self) -> std::io::Result<()> {
+        self.temp_path.rename_to(&self.final_path).await?;
+        Ok(())
+    }
+
+    /// Remove the temporary file.
+    ///
+    /// If the removal fails, the temporary file is left in place.
+    pub async fn abort(self) -> std::io::Result<()> {
+        match self.temp_path.try_drop().await {
+            Ok(_) => Ok(()),
+            Err((mut temp_path, error)) => {
+                temp_path.persist();
+                Err(error)
+            }
+        }
+    }
+}
+
+impl AsRef<Path> for TempToFinalPath {
+    fn as_ref(&self) -> &Path {
+        self.temp_path.as_ref()
+    }
+}
+
+impl Deref for TempToFinalPath {
+    type Target = Path;
+
+    fn deref(&self) -> &Self::Target {
+        &self.temp_path
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[tokio::test]
+    async fn temp_to_final_path_sanity_check() {
+        tokio::fs::create_dir_all("test_tmp")
+            .await
+            .expect("failed to create tmp dir");
+
+        let temp_path: &Path = "test_tmp/temp_to_final_path.txt.tmp".as_ref();
+        let final_path: &Path = "test_tmp/temp_to_final_path.txt".as_ref();
+        let _ = tokio::fs::remove_file(final_path).await;
+
+        let path = TempToFinalPath::new(temp_path, final_path);
+        tokio::fs::write(&*path, "data")
+            .await
+            .expect("failed to write file");
+        path.abort().await.expect("failed to abort");
+        assert!(!temp_path.exists(), "aborted file exists");
+        assert!(!final_path.exists(), "aborted file was committed");
+
+        let path = TempToFinalPath::new(temp_path, final_path);
+        tokio::fs::write(&*path, "data")
+            .await
+            .expect("failed to write file");
+        path.commit().await.expect("failed to commit");
+        assert!(!temp_path.exists(), "committed file exists at temp path");
+        assert!(final_path.exists(), "committed file does not exist");
+    }
+}
@@ -0,0 +1,110 @@
+use crate::DropRemovePathBlocking;
+use std::ops::Deref;
+use std::path::Path;
+use std::path::PathBuf;
+
+/// A temporary path that is renamed to a final path on success, blocking the current thread.
+///
+/// This is the blocking version of [`TempToFinalPath`](crate::TempToFinalPath).
+/// If neither [`TempToFinalPathBlocking::commit`] nor [`TempToFinalPathBlocking::abort`] is called,
+/// the temporary file is removed on drop, like a [`DropRemovePathBlocking`].
+#[derive(Debug)]
+pub struct TempToFinalPathBlocking {
+    /// The temporary path
+    temp_path: DropRemovePathBlocking,
+
+    /// The final path
+    final_path: PathBuf,
+}
+
+impl TempToFinalPathBlocking {
+    /// Make a new [`TempToFinalPathBlocking`].
+    pub fn new<P, Q>(temp_path: P, final_path: Q) -> Self
+    where
+        P: AsRef<Path>,
+        Q: AsRef<Path>,
+    {
+        Self {
+            temp_path: DropRemovePathBlocking::new(temp_path),
+            final_path: final_path.as_ref().into(),
+        }
+    }
+
+    /// Get the temporary path.
+    pub fn temp_path(&self) -> &Path {
+        &self.temp_path
+    }
+
+    /// Get the final path.
+    pub fn final_path(&self) -> &Path {
+        &self.final_path
+    }
+
+    /// Rename the temporary file to the final path.
+    ///
+    /// If the rename fails, the temporary file is removed.
+    pub fn commit(self) -> std::io::Result<()> {
+        self.temp_path.rename_to(&self.final_path)?;
+        Ok(())
+    }
+
+    /// Remove the temporary file.
+    ///
+    /// If the removal fails, the temporary file is left in place.
+    pub fn abort(self) -> std::io::Result<()> {
+        match self.temp_path.try_drop() {
+            Ok(_) => Ok(()),
+            Err((mut temp_path, error)) => {
+                temp_path.persist();
+                Err(error)
+            }
+        }
+    }
+}
+
+impl AsRef<Path> for TempToFinalPathBlocking {
+    fn as_ref(&self) -> &Path {
+        self.temp_path.as_ref()
+    }
+}
+
+impl Deref for TempToFinalPathBlocking {
+    type Target = Path;
+
+    fn deref(&self) -> &Self::Target {
+        &self.temp_path
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn temp_to_final_path_blocking_sanity_check() {
+        std::fs::create_dir_all("test_tmp").expect("failed to create tmp dir");
+
+        let temp_path: &Path = "test_tmp/temp_to_final_path_blocking.txt.tmp".as_ref();
+        let final_path: &Path = "test_tmp/temp_to_final_path_blocking.txt".as_ref();
+        let _ = std::fs::remove_file(final_path);
+
+        let path = TempToFinalPathBlocking::new(temp_path, final_path);
+        std::fs::write(&*path, "data").expect("failed to write file");
+        drop(path);
+        assert!(!temp_path.exists(), "dropped file exists");
+
+        let path = TempToFinalPathBlocking::new(temp_path, final_path);
+        std::fs::write(&*path, "data").expect("failed to write file");
+        path.abort().expect("failed to abort");
+        assert!(!temp_path.exists(), "aborted file exists");
+        assert!(!final_path.exists(), "aborted file was committed");
+
+        let path = TempToFinalPathBlocking::new(temp_path, final_path);
+        assert!(path.temp_path() == temp_path);
+        assert!(path.final_path() == final_path);
+        std::fs::write(&*path, "data").expect("failed to write file");
+        path.commit().expect("failed to commit");
+        assert!(!temp_path.exists(), "committed file exists at temp path");
+        assert!(final_path.exists(), "committed file does not exist");
+    }
+}
@@ -0,0 +1,239 @@
+use std::io::BufRead;
+use std::io::BufReader;
+use std::io::Read;
+use std::io::Write;
+use std::net::TcpListener;
+use std::net::TcpStream;
+#[cfg(any(
+    feature = "download-to-file-pinned",
+    feature = "download-to-path-pinned"
+))]
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use std::time::Duration;
+use std::time::Instant;
+
+/// The longest the server waits for a request, and the longest [`TestServer::join`] waits for the server,
+/// so that a test fails instead of hanging if the code under test sends fewer requests than expected.
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+/// A connection to the server, which may be over TLS.
+trait Stream: Read + Write {}
+
+impl<T> Stream for T where T: Read + Write {}
+
+/// A tiny HTTP server for tests.
+///
+/// Each canned response is sent to one connection, in order.
+/// Joining the server returns the raw head of each request it received.
+pub struct TestServer {
+    /// The base url of the server, like "http://127.0.0.1:1234".
+    pub url: String,
+
+    /// The server thread.
+    handle: JoinHandle<Vec<String>>,
+}
+
+impl TestServer {
+    /// Spawn a server that answers with the given responses.
+    pub fn spawn<I>(responses: I) -> Self
+    where
+        I: IntoIterator<Item = Vec<u8>>,
+    {
+        let mut responses: Vec<_> = responses.into_iter().collect();
+        responses.reverse();
+
+        Self::spawn_with(responses.len(), move |_head| responses.pop().unwrap())
+    }
+
+    /// Spawn a server that answers `count` requests,
+    /// using the given function to make a response from the head of each request.
+    pub fn spawn_with<F>(count: usize, handler: F) -> Self
+    where
+        F: FnMut(&str) -> Vec<u8> + Send + 'static,
+    {
+        Self::spawn_with_stall(count, Duration::ZERO, handler)
+    }
+
+    /// Spawn a server that answers one request with the given response,
+    /// then keeps the connection open for `stall` before closing it.
+    ///
+    /// This is useful for simulating a server that stops sending a body partway through.
+    #[cfg(feature = "download-to-path")]
+    pub fn spawn_stalled(response: Vec<u8>, stall: Duration) -> Self {
+        let mut response = Some(response);
+        Self::spawn_with_stall(1, stall, move |_head| response.take().unwrap())
+    }
+
+    /// Spawn a server that answers `count` requests like [`TestServer::spawn_with`],
+    /// waiting for `stall` after sending each response before closing the connection.
+    pub fn spawn_with_stall<F>(count: usize, stall: Duration, handler: F) -> Self
+    where
+        F: FnMut(&str) -> Vec<u8> + Send + 'static,
+    {
+        Self::spawn_inner(count, stall, "http", |stream| Box::new(stream), handler)
+    }
+
+    /// Spawn a server that answers with the given responses over TLS,
+    /// presenting the given DER certificate and PKCS #8 private key.
+    ///
+    /// The url of the server uses "https" and the ip address 127.0.0.1,
+    /// so the certificate must be valid for that address.
+    #[cfg(any(
+        feature = "download-to-file-pinned",
+        feature = "download-to-path-pinned"
+    ))]
+    pub fn spawn_tls<I>(responses: I, cert_der: &[u8], key_der: &[u8]) -> Self
+    where
+        I: IntoIterator<Item = Vec<u8>>,
+    {
+        use rustls::pki_types::CertificateDer;
+        use rustls::pki_types::PrivateKeyDer;
+        use rustls::pki_types::PrivatePkcs8KeyDer;
+
+        let config = rustls::ServerConfig::builder_with_provider(Arc::new(
+            rustls::crypto::ring::default_provider(),
+        ))
+        .with_safe_default_protocol_versions()
+        .expect("failed to choose tls versions")
+        .with_no_client_auth()
+        .with_single_cert(
+            vec![CertificateDer::from(cert_der.to_vec())],
+            PrivateKeyDer::Pkcs8(PrivatePkcs8KeyDer::from(key_der.to_vec())),
+        )
+        .expect("invalid certificate or key");
+
+        let mut responses: Vec<_> = responses.into_iter().collect();
+        responses.reverse();
+
+        let config = Arc::new(config);
+        Self::spawn_inner(
+            responses.len(),
+            Duration::ZERO,
+            "https",
+            move |stream| {
+                let connection = rustls::ServerConnection::new(config.clone())
+                    .expect("failed to make tls connection");
+                Box::new(rustls::StreamOwned::new(connection, stream))
+            },
+            move |_head| responses.pop().unwrap(),
+        )
+    }
+
+    /// Spawn a server, using `wrap` to turn each accepted connection into a stream for the given scheme.
+    fn spawn_inner<W, F>(
+        count: usize,
+        stall: Duration,
+        scheme: &str,
+        wrap: W,
+        mut handler: F,
+    ) -> Self
+    where
+        W: Fn(TcpStream) -> Box<dyn Stream> + Send + 'static,
+        F: FnMut(&str) -> Vec<u8> + Send + 'static,
+    {
+        let listener = TcpListener::bind("127.0.0.1:0").expect("failed to bind");
+        let url = format!("{scheme}://{}", listener.local_addr().unwrap());
+
+        // Accepting is polled, so that it can give up after the timeout.
+        listener
+            .set_nonblocking(true)
+            .expect("failed to make listener nonblocking");
+
+        let handle = std::thread::spawn(move || {
+            let mut requests = Vec::with_capacity(count);
+            for i in 0..count {
+                let deadline = Instant::now() + TIMEOUT;
+                let stream = loop {
+                    match listener.accept() {
+                        Ok((stream, _)) => break stream,
+                        Err(error) if error.kind() == std::io::ErrorKind::WouldBlock => {
+                            assert!(
+                                Instant::now() < deadline,
+                                "timed out waiting for request {} of {count}",
+                                i + 1
+                            );
+                            std::thread::sleep(Duration::from_millis(10));
+                        }
+                        Err(error) => panic!("failed to accept: {error}"),
+                    }
+                };
+                stream
+                    .set_nonblocking(false)
+                    .expect("failed to make stream blocking");
+                stream
+                    .set_read_timeout(Some(TIMEOUT))
+                    .expect("failed to set read timeout");
+                let mut reader = BufReader::new(wrap(stream));
+
+                // Read the request head.
+                let mut head = String::new();
+                loop {
+                    let mut line = String::new();
+                    reader.read_line(&mut line).expect("failed to read line");
+                    if line == "\r\n" || line.is_empty() {
+                        break;
+                    }
+                    head.push_str(&line);
+                }
+
+                // Skip the request body.
+                let content_length = head
+                    .lines()
+                    .filter_map(|line| line.split_once(':'))
+                    .find(|(name, _)| name.eq_ignore_ascii_case("content-length"))
+                    .map(|(_, value)| value.trim().parse::<u64>().unwrap())
+                    .unwrap_or(0);
+                std::io::copy(
+                    &mut reader.by_ref().take(content_length),
+                    &mut std::io::sink(),
+                )
+                .expect("failed to read body");
+
+                let response = handler(&head);
+                let mut stream = reader.into_inner();
+                stream.write_all(&response).expect("failed to write");
+                stream.flush().expect("failed to flush");
+                std::thread::sleep(stall);
+                requests.push(head);
+            }
+            requests
+        });
+
+        Self { url, handle }
+    }
+
+    /// Wait for all responses to be sent, returning the request heads.
+    ///
+    /// # Panics
+    /// Panics if the server panicked, or if it is still running after the timeout.
+    pub fn join(self) -> Vec<String> {
+        // The server gives up on each request after the timeout,
+        // so this only runs out if a handler or stall takes too long.
+        let deadline = Instant::now() + TIMEOUT * 2;
+        while !self.handle.is_finished() {
+            assert!(
+                Instant::now() < deadline,
+                "timed out waiting for the server"
+            );
+            std::thread::sleep(Duration::from_millis(10));
+        }
+
+        self.handle.join().expect("server panicked")
+    }
+}
+
+/// Make a simple response with the given status line, headers, and body.
+pub fn response(status: &str, headers: &[&str], body: &[u8]) -> Vec<u8> {
+    let mut response = format!("HTTP/1.1 {status}\r\n");
+    for header in headers {
+        response.push_str(header);
+        response.push_str("\r\n");
+    }
+    response.push_str(&format!("Content-Length: {}\r\n", body.len()));
+    response.push_str("Connection: close\r\n\r\n");
+
+    let mut response = response.into_bytes();
+    response.extend(body);
+    response
+}
@@ -0,0 +1,100 @@
+use anyhow::Context;
+use notify::RecursiveMode;
+use notify::Watcher;
+use std::future::Future;
+use std::path::Path;
+use std::sync::mpsc::RecvTimeoutError;
+use std::time::Duration;
+
+/// How often the file is checked, in case the watcher missed an event.
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Watch a file, returning a future that resolves once it is deleted.
+///
+/// This is useful for coordinating with another process, like waiting for it to remove a lock file.
+/// The directory of the file is watched with the platform's file watching API,
+/// which is inotify on Linux, FSEvents on macOS, and `ReadDirectoryChangesW` on Windows,
+/// from a blocking thread started through [`tokio::task::spawn_blocking`].
+/// Since watchers can miss events, the file is also checked periodically.
+/// A file that is renamed away counts as deleted.
+///
+/// The watch starts when this is called, so a deletion right after this returns is not missed.
+/// If the file does not exist, the future resolves immediately.
+/// Dropping the future stops the watch.
+///
+/// # Errors
+/// Returns an error if the directory of the file could not be watched.
+pub fn watch_file_for_deletion(path: &Path) -> anyhow::Result<impl Future<Output = ()>> {
+    let path = path.to_path_buf();
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir.to_path_buf(),
+        _ => ".".into(),
+    };
+
+    // Start watching before checking the file, so a deletion in between is not missed.
+    let (event_tx, event_rx) = std::sync::mpsc::channel();
+    let mut watcher = notify::recommended_watcher(event_tx).context("failed to create watcher")?;
+    watcher
+        .watch(&dir, RecursiveMode::NonRecursive)
+        .with_context(|| format!("failed to watch \"{}\"", dir.display()))?;
+
+    Ok(async move {
+        let (deleted_tx, deleted_rx) = tokio::sync::oneshot::channel();
+        tokio::task::spawn_blocking(move || {
+            // Keep the watcher alive while waiting.
+            let _watcher = watcher;
+
+            // Errors checking the file are treated like it exists, so they don't end the watch early.
+            while path.try_exists().unwrap_or(true) {
+                // The future was dropped.
+                if deleted_tx.is_closed() {
+                    return;
+                }
+
+                // Any event might be the deletion, so there is no need to look at it.
+                if let Err(RecvTimeoutError::Disconnected) = event_rx.recv_timeout(POLL_INTERVAL) {
+                    std::thread::sleep(POLL_INTERVAL);
+                }
+            }
+
+            let _ = deleted_tx.send(());
+        });
+
+        // The task only stops early if this future is dropped.
+        let _ = deleted_rx.await;
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[tokio::test]
+    async fn watch_file_for_deletion_works() {
+        std::fs::create_dir_all("test_tmp").expect("failed to create tmp dir");
+
+        let path = Path::new("test_tmp/watch_file_for_deletion.txt");
+        std::fs::write(path, "data").expect("failed to write file");
+
+        let deleted = tokio::spawn(watch_file_for_deletion(path).expect("failed to watch file"));
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(
+            !deleted.is_finished(),
+            "resolved before the file was deleted"
+        );
+
+        std::fs::remove_file(path).expect("failed to remove file");
+        tokio::time::timeout(Duration::from_secs(5), deleted)
+            .await
+            .expect("did not resolve after the file was deleted")
+            .expect("failed to join task");
+
+        // Missing files resolve immediately.
+        tokio::time::timeout(
+            Duration::from_secs(5),
+            watch_file_for_deletion(path).expect("failed to watch file"),
+        )
+        .await
+        .expect("did not resolve for a missing file");
+    }
+}
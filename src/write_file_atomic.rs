@@ -0,0 +1,251 @@
+use crate::with_push_extension;
+use std::path::Path;
+
+/// The extension added to the path of the temporary file.
+const TEMP_EXTENSION: &str = "tmp";
+
+/// Atomically write data to a file at the given path.
+///
+/// The data is written to a temporary file next to the path, like "file.txt.tmp".
+/// This file is synced to disk, then renamed to the path.
+/// As a result, the file at the path will either contain the old data or the new data, never a mix.
+/// If writing fails, the temporary file is removed.
+///
+/// Callers must ensure that only one writer targets a given path at a time,
+/// as concurrent writers share the same temporary file.
+#[cfg(feature = "write-file-atomic")]
+pub fn write_file_atomic(path: &Path, data: &[u8]) -> std::io::Result<()> {
+    use crate::DropRemovePathBlocking;
+    use std::io::Write;
+
+    let temp_path = with_push_extension(path, TEMP_EXTENSION);
+
+    let mut file = std::fs::File::create(&temp_path)?;
+    let mut temp_path = DropRemovePathBlocking::new(temp_path);
+
+    file.write_all(data)?;
+    file.sync_all()?;
+    drop(file);
+
+    std::fs::rename(&temp_path, path)?;
+    temp_path.persist();
+
+    Ok(())
+}
+
+/// Atomically write data to a file at the given path, unless the file already has that data.
+///
+/// This is useful for code generators, where rewriting a file with the same data would cause a spurious rebuild.
+/// The existing file is read and compared with the data.
+/// If it differs or there is no file, the data is written with [`write_file_atomic`].
+///
+/// # Returns
+/// Returns `Ok(true)` if the file was written.
+/// Returns `Ok(false)` if the file already had the data.
+#[cfg(feature = "write-file-atomic")]
+pub fn write_file_if_changed(path: &Path, data: &[u8]) -> std::io::Result<bool> {
+    if is_unchanged(std::fs::read(path), data)? {
+        return Ok(false);
+    }
+
+    write_file_atomic(path, data)?;
+
+    Ok(true)
+}
+
+/// Check whether the result of reading a file matches the given data.
+///
+/// A missing file does not match.
+fn is_unchanged(existing: std::io::Result<Vec<u8>>, data: &[u8]) -> std::io::Result<bool> {
+    match existing {
+        Ok(existing) => Ok(existing == data),
+        Err(error) if error.kind() == std::io::ErrorKind::NotFound => Ok(false),
+        Err(error) => Err(error),
+    }
+}
+
+/// Read a file while holding a shared advisory lock on it.
+///
+/// Other readers with shared locks can read at the same time,
+/// while anything that takes an exclusive advisory lock on the same file,
+/// like the `FileLock` of the `file-lock` feature, waits until the read is done.
+/// The lock is released when the file is closed.
+/// On platforms that don't support advisory locking, the file is read without a lock.
+///
+/// The lock does not block [`write_file_atomic`], which takes no lock.
+/// It replaces the file by renaming a new file over the path,
+/// which never changes a file that is already open,
+/// so a read sees either all of the old data or all of the new data either way.
+#[cfg(feature = "write-file-atomic")]
+pub fn read_file_with_lock(path: &Path) -> anyhow::Result<Vec<u8>> {
+    use anyhow::Context;
+    use std::io::Read;
+
+    let mut file = std::fs::File::open(path)
+        .with_context(|| format!("failed to open \"{}\"", path.display()))?;
+    match file.lock_shared() {
+        Ok(()) => {}
+        Err(error) if error.kind() == std::io::ErrorKind::Unsupported => {}
+        Err(error) => {
+            return Err(error).with_context(|| format!("failed to lock \"{}\"", path.display()))
+        }
+    }
+
+    let mut data = Vec::new();
+    file.read_to_end(&mut data)
+        .with_context(|| format!("failed to read \"{}\"", path.display()))?;
+
+    Ok(data)
+}
+
+/// Atomically write data to a file at the given path, asynchronously.
+///
+/// This is the async version of [`write_file_atomic`](crate::write_file_atomic).
+#[cfg(feature = "write-file-atomic-async")]
+pub async fn write_file_atomic_async(path: &Path, data: &[u8]) -> std::io::Result<()> {
+    use crate::DropRemovePath;
+    use tokio::io::AsyncWriteExt;
+
+    let temp_path = with_push_extension(path, TEMP_EXTENSION);
+
+    let mut file = tokio::fs::File::create(&temp_path).await?;
+    let mut temp_path = DropRemovePath::new(temp_path);
+
+    let result = async {
+        file.write_all(data).await?;
+        file.sync_all().await?;
+        drop(file);
+
+        tokio::fs::rename(&temp_path, path).await
+    }
+    .await;
+
+    match result {
+        Ok(()) => {
+            temp_path.persist();
+            Ok(())
+        }
+        Err(error) => {
+            // Try to clean up now instead of in the background.
+            if let Err((mut temp_path, _)) = temp_path.try_drop().await {
+                temp_path.persist();
+            }
+            Err(error)
+        }
+    }
+}
+
+/// Atomically write data to a file at the given path, unless the file already has that data, asynchronously.
+///
+/// This is the async version of [`write_file_if_changed`](crate::write_file_if_changed),
+/// which compares the data the same way.
+///
+/// # Returns
+/// Returns `Ok(true)` if the file was written.
+/// Returns `Ok(false)` if the file already had the data.
+#[cfg(feature = "write-file-atomic-async")]
+pub async fn write_file_if_changed_async(path: &Path, data: &[u8]) -> std::io::Result<bool> {
+    if is_unchanged(tokio::fs::read(path).await, data)? {
+        return Ok(false);
+    }
+
+    write_file_atomic_async(path, data).await?;
+
+    Ok(true)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[cfg(feature = "write-file-atomic")]
+    #[test]
+    fn write_file_atomic_works() {
+        std::fs::create_dir_all("test_tmp").expect("failed to create tmp dir");
+
+        let path = Path::new("test_tmp/write_file_atomic.txt");
+        write_file_atomic(path, b"old").expect("failed to write");
+        write_file_atomic(path, b"new").expect("failed to write");
+
+        assert!(std::fs::read(path).expect("failed to read") == b"new");
+        assert!(!Path::new("test_tmp/write_file_atomic.txt.tmp").exists());
+    }
+
+    #[cfg(feature = "write-file-atomic")]
+    #[test]
+    fn write_file_if_changed_works() {
+        std::fs::create_dir_all("test_tmp").expect("failed to create tmp dir");
+
+        let path = Path::new("test_tmp/write_file_if_changed.txt");
+        let _ = std::fs::remove_file(path);
+
+        assert!(write_file_if_changed(path, b"data").expect("failed to write"));
+        assert!(!write_file_if_changed(path, b"data").expect("failed to write"));
+        assert!(write_file_if_changed(path, b"new data").expect("failed to write"));
+        assert!(std::fs::read(path).expect("failed to read") == b"new data");
+    }
+
+    #[cfg(feature = "write-file-atomic")]
+    #[test]
+    fn read_file_with_lock_works() {
+        std::fs::create_dir_all("test_tmp").expect("failed to create tmp dir");
+
+        let path = Path::new("test_tmp/read_file_with_lock.txt");
+        write_file_atomic(path, b"data").expect("failed to write");
+
+        // Shared locks don't exclude each other.
+        let other = std::fs::File::open(path).expect("failed to open");
+        other.lock_shared().expect("failed to lock");
+        assert!(read_file_with_lock(path).expect("failed to read") == b"data");
+        drop(other);
+
+        read_file_with_lock(Path::new("test_tmp/read_file_with_lock_missing.txt"))
+            .expect_err("read a missing file");
+    }
+
+    #[cfg(feature = "write-file-atomic-async")]
+    #[tokio::test]
+    async fn write_file_atomic_async_works() {
+        tokio::fs::create_dir_all("test_tmp")
+            .await
+            .expect("failed to create tmp dir");
+
+        let path = Path::new("test_tmp/write_file_atomic_async.txt");
+        write_file_atomic_async(path, b"old")
+            .await
+            .expect("failed to write");
+        write_file_atomic_async(path, b"new")
+            .await
+            .expect("failed to write");
+
+        assert!(tokio::fs::read(path).await.expect("failed to read") == b"new");
+        assert!(!Path::new("test_tmp/write_file_atomic_async.txt.tmp").exists());
+
+        let path = Path::new("test_tmp/missing_dir/write_file_atomic_async.txt");
+        write_file_atomic_async(path, b"data")
+            .await
+            .expect_err("wrote to missing dir");
+    }
+
+    #[cfg(feature = "write-file-atomic-async")]
+    #[tokio::test]
+    async fn write_file_if_changed_async_works() {
+        tokio::fs::create_dir_all("test_tmp")
+            .await
+            .expect("failed to create tmp dir");
+
+        let path = Path::new("test_tmp/write_file_if_changed_async.txt");
+        let _ = tokio::fs::remove_file(path).await;
+
+        assert!(write_file_if_changed_async(path, b"data")
+            .await
+            .expect("failed to write"));
+        assert!(!write_file_if_changed_async(path, b"data")
+            .await
+            .expect("failed to write"));
+        assert!(write_file_if_changed_async(path, b"new data")
+            .await
+            .expect("failed to write"));
+        assert!(tokio::fs::read(path).await.expect("failed to read") == b"new data");
+    }
+}